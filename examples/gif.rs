@@ -0,0 +1,50 @@
+// Render one full turn of the donut to a looping GIF using only the library, as a starting point
+// for using it in other programs:
+//
+//   cargo run --release --example gif -- donut.gif
+
+use donut::{post, ColorDepth, RenderConfig};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use std::f32::consts::PI;
+
+const SIZE: usize = 200;
+const FRAMES: usize = 60;
+
+// How long each frame shows for, in milliseconds
+const FRAME_DELAY: u32 = 40;
+
+fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "donut.gif".to_string());
+
+    // A thicker donut shaded from deep purple through pink to white
+    let mut config = RenderConfig::new(SIZE, SIZE);
+    config.r1 = 1.3;
+    config.r2 = 1.8;
+    config.palette = vec![0x1a0033, 0x4b0082, 0x9b30ff, 0xff69b4, 0xffc0cb, 0xffffff];
+
+    let mut frames = Vec::with_capacity(FRAMES);
+    for i in 0..FRAMES {
+        // One full turn around both axes, so the last frame leads back round to the first
+        let angle = i as f32 * 2.0 * PI / FRAMES as f32;
+        let mut output = donut::frame(&config, angle, angle);
+
+        // GIFs have at most 256 colors a frame, and RGB332 has exactly that many, so every frame
+        // fits without the encoder having to pick a palette for it
+        post::reduce_color_depth(&mut output, ColorDepth::Rgb332);
+
+        let image = RgbaImage::from_fn(SIZE as u32, SIZE as u32, |x, y| {
+            let pixel = output[y as usize * SIZE + x as usize];
+            image::Rgba([(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8, 0xff])
+        });
+        let delay = Delay::from_numer_denom_ms(FRAME_DELAY, 1);
+        frames.push(Frame::from_parts(image, 0, 0, delay));
+    }
+
+    let file = std::fs::File::create(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    let mut encoder = GifEncoder::new(file);
+    encoder.set_repeat(Repeat::Infinite).unwrap();
+    encoder.encode_frames(frames).unwrap();
+}
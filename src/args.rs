@@ -0,0 +1,453 @@
+// Command line options. Anything not given here falls back to the constants in main.rs.
+#[derive(Default)]
+pub struct Args {
+    pub ascii_braille: bool,
+    pub background_image: Option<String>,
+    pub row: Option<usize>,
+    pub checker: Option<usize>,
+    pub texture: Option<String>,
+    pub explode: Option<usize>,
+    pub grain: Option<f32>,
+    pub bloom: Option<f32>,
+    pub bloom_threshold: Option<f32>,
+    pub shading_gamma: Option<f32>,
+    pub cel: Option<usize>,
+    pub fur: Option<f32>,
+    pub debug_axes: bool,
+    pub debug_density: bool,
+    pub crop: Option<[f32; 4]>,
+    pub cage: Option<u32>,
+    pub cull_threshold: Option<f32>,
+    pub sparkle: Option<f32>,
+    pub ring: Option<usize>,
+    pub ring_radius: Option<f32>,
+    pub drive_stdin: bool,
+    pub z_bias: Option<f32>,
+    pub z_supersample: Option<usize>,
+    pub camera_orbit: bool,
+    pub terminator_deadzone: Option<f32>,
+    pub mp4: Option<String>,
+    pub a_range: Option<(f32, f32)>,
+    pub b_range: Option<(f32, f32)>,
+    pub outline_glow: Option<u32>,
+    pub outline_glow_radius: Option<usize>,
+    pub outline_glow_intensity: Option<f32>,
+    pub proc_palette: Option<u64>,
+    pub proc_palette_frames: Option<u64>,
+    pub crisp_outline: bool,
+    pub debug_overdraw: bool,
+    pub shell_thickness: Option<f32>,
+    pub z_blend_epsilon: Option<donut::Depth>,
+    pub explode_amplitude: Option<f32>,
+    pub explode_period: Option<u64>,
+    pub reflection: Option<f32>,
+    pub palette_fade: Option<u64>,
+    pub capture_turns: Option<String>,
+    pub gradient_scroll: Option<f32>,
+    pub stats: bool,
+    pub preset: Option<String>,
+    pub list_presets: bool,
+    pub splat: Option<f32>,
+    pub color_depth: Option<donut::ColorDepth>,
+    pub theta_spacing: Option<f32>,
+    pub phi_spacing: Option<f32>,
+    pub auto_tune: bool,
+    pub fbdev: Option<String>,
+    pub easing: Option<donut::Easing>,
+    pub region: Option<crate::braille::Region>,
+    pub lum_floor: Option<f32>,
+    pub lum_ceil: Option<f32>,
+    pub cosine_palette: Option<donut::CosinePalette>,
+    pub speed_a: Option<f32>,
+    pub speed_b: Option<f32>,
+    pub phase_offset: Option<f32>,
+    pub scale: Option<[f32; 3]>,
+    pub record_input: Option<String>,
+    pub replay_input: Option<String>,
+    pub max_speed: bool,
+    pub vignette: Option<f32>,
+    pub point_size: Option<usize>,
+    pub png16: Option<String>,
+    pub fade_in: Option<u64>,
+    pub reveal: Option<u64>,
+    pub resume: bool,
+    pub interlace: bool,
+    pub quiet: bool,
+    pub shape: Option<donut::Shape>,
+    pub shake_amplitude: Option<f32>,
+    pub shake_decay: Option<f32>,
+    pub anaglyph: bool,
+    pub eye_separation: Option<f32>,
+    pub spin_up: Option<u64>,
+    pub inner_color: Option<u32>,
+    pub outer_color: Option<u32>,
+    pub palette: Option<Vec<u32>>,
+    pub scanlines: Option<f32>,
+    pub morph: Option<f32>,
+    pub morph_period: Option<u64>,
+    pub wobble: Option<f32>,
+    pub wobble_frequency: Option<f32>,
+    pub char_aspect: Option<f32>,
+    pub mono: Option<u32>,
+    pub r1: Option<f32>,
+    pub r2: Option<f32>,
+    pub dump_config: bool,
+    pub auto_frame: Option<f32>,
+    pub silhouette: Option<u32>,
+    pub random_start: bool,
+    pub z_mode: Option<donut::ZMode>,
+    pub spritesheet: Option<String>,
+    pub frames: Option<usize>,
+    pub cols: Option<usize>,
+    pub melt: Option<u64>,
+}
+
+fn value(args: &mut impl Iterator<Item = String>, flag: &str) -> String {
+    args.next().unwrap_or_else(|| {
+        eprintln!("{} requires a value", flag);
+        std::process::exit(1);
+    })
+}
+
+fn parsed_value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> T {
+    let value = value(args, flag);
+
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid value for {}: {}", flag, value);
+        std::process::exit(1);
+    })
+}
+
+// A number above 0, like a spacing or ratio which would stall or collapse the render at 0
+fn positive_value(args: &mut impl Iterator<Item = String>, flag: &str) -> f32 {
+    let value = value(args, flag);
+
+    match value.parse::<f32>() {
+        Ok(number) if number.is_finite() && number > 0.0 => number,
+        _ => {
+            eprintln!(
+                "Invalid value for {}: {} (expected a number above 0)",
+                flag, value
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+// A whole number of at least 1, for counts which there's no sense in having none of
+fn count_value(args: &mut impl Iterator<Item = String>, flag: &str) -> usize {
+    let value = value(args, flag);
+
+    match value.parse() {
+        Ok(count) if count > 0 => count,
+        _ => {
+            eprintln!(
+                "Invalid value for {}: {} (expected a whole number above 0)",
+                flag, value
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+// A 0xRRGGBB color written as hex, with or without a leading #
+fn parse_color(value: &str) -> Option<u32> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+
+    match u32::from_str_radix(hex, 16) {
+        Ok(color) if hex.len() == 6 => Some(color),
+        _ => None,
+    }
+}
+
+fn color_value(args: &mut impl Iterator<Item = String>, flag: &str) -> u32 {
+    let value = value(args, flag);
+
+    parse_color(&value).unwrap_or_else(|| {
+        eprintln!("Invalid color for {}: {} (expected RRGGBB)", flag, value);
+        std::process::exit(1);
+    })
+}
+
+// A comma separated list of at least one color
+fn palette_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Vec<u32> {
+    let value = value(args, flag);
+
+    let palette: Option<Vec<u32>> = value.split(',').map(parse_color).collect();
+    palette.unwrap_or_else(|| {
+        eprintln!(
+            "Invalid palette for {}: {} (expected RRGGBB,RRGGBB,...)",
+            flag, value
+        );
+        std::process::exit(1);
+    })
+}
+
+// The four coefficients of a cosine palette separated by slashes, each of them three numbers
+// (red, green, and blue) separated by commas
+fn cosine_palette_value(
+    args: &mut impl Iterator<Item = String>,
+    flag: &str,
+) -> donut::CosinePalette {
+    let value = value(args, flag);
+
+    let coefficients: Option<Vec<[f32; 3]>> = value
+        .split('/')
+        .map(|coefficient| {
+            let numbers: Option<Vec<f32>> =
+                coefficient.split(',').map(|s| s.parse().ok()).collect();
+            match numbers.as_deref() {
+                Some(&[r, g, b]) => Some([r, g, b]),
+                _ => None,
+            }
+        })
+        .collect();
+    match coefficients.as_deref() {
+        Some(&[a, b, c, d]) => donut::CosinePalette { a, b, c, d },
+        _ => {
+            eprintln!(
+                "Invalid cosine palette for {}: {} (expected R,G,B/R,G,B/R,G,B/R,G,B for a/b/c/d)",
+                flag, value
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+// A rectangle of the screen in pixels: x and y from its top-left corner, then its width and height
+fn crop_value(args: &mut impl Iterator<Item = String>, flag: &str) -> [f32; 4] {
+    let value = value(args, flag);
+
+    let numbers: Option<Vec<f32>> = value.split(',').map(|s| s.parse().ok()).collect();
+    match numbers.as_deref() {
+        Some(&[x, y, width, height])
+            if [x, y, width, height].iter().all(|n| n.is_finite())
+                && width >= 1.0
+                && height >= 1.0 =>
+        {
+            [x, y, width, height]
+        }
+        _ => {
+            eprintln!(
+                "Invalid crop for {}: {} (expected X,Y,WIDTH,HEIGHT, at least a pixel across)",
+                flag, value
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+// Two numbers separated by a comma, the first smaller than the second, like -0.5,0.5
+fn range_value(args: &mut impl Iterator<Item = String>, flag: &str) -> (f32, f32) {
+    let value = value(args, flag);
+
+    let numbers: Option<Vec<f32>> = value.split(',').map(|s| s.parse().ok()).collect();
+    match numbers.as_deref() {
+        Some(&[min, max]) if min < max => (min, max),
+        _ => {
+            eprintln!(
+                "Invalid range for {}: {} (expected MIN,MAX with MIN below MAX)",
+                flag, value
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+// Three numbers separated by commas, like 1,0.5,1
+fn scale_value(args: &mut impl Iterator<Item = String>, flag: &str) -> [f32; 3] {
+    let value = value(args, flag);
+
+    let scale: Option<Vec<f32>> = value.split(',').map(|s| s.parse().ok()).collect();
+    match scale.as_deref() {
+        Some(&[x, y, z]) => [x, y, z],
+        _ => {
+            eprintln!("Invalid scale for {}: {} (expected X,Y,Z)", flag, value);
+            std::process::exit(1);
+        }
+    }
+}
+
+// A box of the terminal: where its top-left corner is (from 1, 1) and how many columns and rows it
+// covers
+fn region_value(args: &mut impl Iterator<Item = String>, flag: &str) -> crate::braille::Region {
+    let value = value(args, flag);
+
+    let numbers: Option<Vec<usize>> = value.split(',').map(|s| s.parse().ok()).collect();
+    match numbers.as_deref() {
+        Some(&[column, row, columns, rows]) if column > 0 && row > 0 && columns > 0 && rows > 0 => {
+            crate::braille::Region {
+                column,
+                row,
+                columns,
+                rows,
+            }
+        }
+        _ => {
+            eprintln!(
+                "Invalid region for {}: {} (expected COLUMN,ROW,COLUMNS,ROWS, all above 0)",
+                flag, value
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+// A path with {} somewhere in it to fill in
+fn pattern_value(args: &mut impl Iterator<Item = String>, flag: &str) -> String {
+    let value = value(args, flag);
+
+    if !value.contains("{}") {
+        eprintln!(
+            "Invalid path for {}: {} (expected {{}} where the number goes)",
+            flag, value
+        );
+        std::process::exit(1);
+    }
+    value
+}
+
+// The name of one of the built-in presets
+fn preset_value(args: &mut impl Iterator<Item = String>, flag: &str) -> String {
+    let value = value(args, flag);
+
+    if !donut::PRESETS.iter().any(|&(name, _)| name == value) {
+        eprintln!(
+            "Invalid preset for {}: {} (see --list-presets)",
+            flag, value
+        );
+        std::process::exit(1);
+    }
+    value
+}
+
+pub fn parse() -> Args {
+    let mut parsed = Args::default();
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ascii-braille" => parsed.ascii_braille = true,
+            "--background-image" => parsed.background_image = Some(value(&mut args, &arg)),
+            "--row" => parsed.row = Some(parsed_value(&mut args, &arg)),
+            "--checker" => parsed.checker = Some(parsed_value(&mut args, &arg)),
+            "--texture" => parsed.texture = Some(value(&mut args, &arg)),
+            "--explode" => parsed.explode = Some(parsed_value(&mut args, &arg)),
+            "--explode-amplitude" => parsed.explode_amplitude = Some(parsed_value(&mut args, &arg)),
+            "--explode-period" => parsed.explode_period = Some(count_value(&mut args, &arg) as u64),
+            "--grain" => parsed.grain = Some(parsed_value(&mut args, &arg)),
+            "--bloom" => parsed.bloom = Some(parsed_value(&mut args, &arg)),
+            "--bloom-threshold" => parsed.bloom_threshold = Some(parsed_value(&mut args, &arg)),
+            "--shading-gamma" => parsed.shading_gamma = Some(parsed_value(&mut args, &arg)),
+            "--cel" => parsed.cel = Some(parsed_value(&mut args, &arg)),
+            "--fur" => parsed.fur = Some(parsed_value(&mut args, &arg)),
+            "--debug-axes" => parsed.debug_axes = true,
+            "--debug-density" => parsed.debug_density = true,
+            "--debug-overdraw" => parsed.debug_overdraw = true,
+            "--crop" => parsed.crop = Some(crop_value(&mut args, &arg)),
+            "--cage" => parsed.cage = Some(color_value(&mut args, &arg)),
+            "--cull-threshold" => parsed.cull_threshold = Some(parsed_value(&mut args, &arg)),
+            "--sparkle" => parsed.sparkle = Some(parsed_value(&mut args, &arg)),
+            "--ring" => parsed.ring = Some(parsed_value(&mut args, &arg)),
+            "--ring-radius" => parsed.ring_radius = Some(parsed_value(&mut args, &arg)),
+            "--drive-stdin" => parsed.drive_stdin = true,
+            "--z-bias" => parsed.z_bias = Some(parsed_value(&mut args, &arg)),
+            "--z-blend-epsilon" => parsed.z_blend_epsilon = Some(parsed_value(&mut args, &arg)),
+            "--z-supersample" => parsed.z_supersample = Some(parsed_value(&mut args, &arg)),
+            "--camera-orbit" => parsed.camera_orbit = true,
+            "--mp4" => parsed.mp4 = Some(value(&mut args, &arg)),
+            "--a-range" => parsed.a_range = Some(range_value(&mut args, &arg)),
+            "--b-range" => parsed.b_range = Some(range_value(&mut args, &arg)),
+            "--crisp-outline" => parsed.crisp_outline = true,
+            "--shell-thickness" => parsed.shell_thickness = Some(parsed_value(&mut args, &arg)),
+            "--outline-glow" => parsed.outline_glow = Some(color_value(&mut args, &arg)),
+            "--proc-palette" => parsed.proc_palette = Some(parsed_value(&mut args, &arg)),
+            "--proc-palette-frames" => {
+                parsed.proc_palette_frames = Some(parsed_value(&mut args, &arg))
+            }
+            "--outline-glow-radius" => {
+                parsed.outline_glow_radius = Some(parsed_value(&mut args, &arg))
+            }
+            "--outline-glow-intensity" => {
+                parsed.outline_glow_intensity = Some(parsed_value(&mut args, &arg))
+            }
+            "--terminator-deadzone" => {
+                parsed.terminator_deadzone = Some(parsed_value(&mut args, &arg))
+            }
+            "--reflection" => parsed.reflection = Some(parsed_value(&mut args, &arg)),
+            "--palette-fade" => parsed.palette_fade = Some(parsed_value(&mut args, &arg)),
+            "--capture-turns" => parsed.capture_turns = Some(pattern_value(&mut args, &arg)),
+            "--gradient-scroll" => parsed.gradient_scroll = Some(parsed_value(&mut args, &arg)),
+            "--stats" => parsed.stats = true,
+            "--preset" => parsed.preset = Some(preset_value(&mut args, &arg)),
+            "--list-presets" => parsed.list_presets = true,
+            "--splat" => parsed.splat = Some(positive_value(&mut args, &arg)),
+            "--color-depth" => parsed.color_depth = Some(parsed_value(&mut args, &arg)),
+            "--theta-spacing" => parsed.theta_spacing = Some(positive_value(&mut args, &arg)),
+            "--phi-spacing" => parsed.phi_spacing = Some(positive_value(&mut args, &arg)),
+            "--auto-tune" => parsed.auto_tune = true,
+            "--fbdev" => parsed.fbdev = Some(value(&mut args, &arg)),
+            "--easing" => parsed.easing = Some(parsed_value(&mut args, &arg)),
+            "--region" => parsed.region = Some(region_value(&mut args, &arg)),
+            "--lum-floor" => parsed.lum_floor = Some(parsed_value(&mut args, &arg)),
+            "--lum-ceil" => parsed.lum_ceil = Some(parsed_value(&mut args, &arg)),
+            "--cosine-palette" => {
+                parsed.cosine_palette = Some(cosine_palette_value(&mut args, &arg))
+            }
+            "--speed-a" => parsed.speed_a = Some(parsed_value(&mut args, &arg)),
+            "--speed-b" => parsed.speed_b = Some(parsed_value(&mut args, &arg)),
+            "--phase-offset" => parsed.phase_offset = Some(parsed_value(&mut args, &arg)),
+            "--scale" => parsed.scale = Some(scale_value(&mut args, &arg)),
+            "--record-input" => parsed.record_input = Some(value(&mut args, &arg)),
+            "--replay-input" => parsed.replay_input = Some(value(&mut args, &arg)),
+            "--max-speed" => parsed.max_speed = true,
+            "--vignette" => parsed.vignette = Some(parsed_value(&mut args, &arg)),
+            "--point-size" => parsed.point_size = Some(parsed_value(&mut args, &arg)),
+            "--png16" => parsed.png16 = Some(value(&mut args, &arg)),
+            "--fade-in" => parsed.fade_in = Some(parsed_value(&mut args, &arg)),
+            "--reveal" => parsed.reveal = Some(parsed_value(&mut args, &arg)),
+            "--resume" => parsed.resume = true,
+            "--interlace" => parsed.interlace = true,
+            "--quiet" => parsed.quiet = true,
+            "--shape" => parsed.shape = Some(parsed_value(&mut args, &arg)),
+            "--shake-amplitude" => parsed.shake_amplitude = Some(parsed_value(&mut args, &arg)),
+            "--shake-decay" => parsed.shake_decay = Some(parsed_value(&mut args, &arg)),
+            "--anaglyph" => parsed.anaglyph = true,
+            "--eye-separation" => parsed.eye_separation = Some(parsed_value(&mut args, &arg)),
+            "--spin-up" => parsed.spin_up = Some(parsed_value(&mut args, &arg)),
+            "--inner-color" => parsed.inner_color = Some(color_value(&mut args, &arg)),
+            "--outer-color" => parsed.outer_color = Some(color_value(&mut args, &arg)),
+            "--palette" => parsed.palette = Some(palette_value(&mut args, &arg)),
+            "--mono" => parsed.mono = Some(color_value(&mut args, &arg)),
+            "--r1" => parsed.r1 = Some(parsed_value(&mut args, &arg)),
+            "--r2" => parsed.r2 = Some(parsed_value(&mut args, &arg)),
+            "--dump-config" => parsed.dump_config = true,
+            "--auto-frame" => parsed.auto_frame = Some(parsed_value(&mut args, &arg)),
+            "--silhouette" => parsed.silhouette = Some(color_value(&mut args, &arg)),
+            "--random-start" => parsed.random_start = true,
+            "--z-mode" => parsed.z_mode = Some(parsed_value(&mut args, &arg)),
+            "--spritesheet" => parsed.spritesheet = Some(value(&mut args, &arg)),
+            "--frames" => parsed.frames = Some(count_value(&mut args, &arg)),
+            "--cols" => parsed.cols = Some(parsed_value(&mut args, &arg)),
+            "--melt" => parsed.melt = Some(parsed_value(&mut args, &arg)),
+            "--scanlines" => parsed.scanlines = Some(parsed_value(&mut args, &arg)),
+            "--morph" => parsed.morph = Some(parsed_value(&mut args, &arg)),
+            "--morph-period" => parsed.morph_period = Some(parsed_value(&mut args, &arg)),
+            "--wobble" => parsed.wobble = Some(parsed_value(&mut args, &arg)),
+            "--wobble-frequency" => parsed.wobble_frequency = Some(parsed_value(&mut args, &arg)),
+            "--char-aspect" => parsed.char_aspect = Some(positive_value(&mut args, &arg)),
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Each eye's view is only rendered for a single donut
+    if parsed.anaglyph && (parsed.row.is_some() || parsed.ring.is_some()) {
+        eprintln!("--anaglyph can't be used with --row or --ring");
+        std::process::exit(1);
+    }
+
+    parsed
+}
@@ -0,0 +1,129 @@
+// Listen to the default input device and track how loud it is, for the `audio` feature. Without
+// the feature (or without an input device) the level is always zero and the animation is unchanged.
+// Unless `quiet`, the reason for falling back is printed to stderr.
+
+#[cfg(feature = "audio")]
+mod input {
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use cpal::{FromSample, SampleFormat, SizedSample};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    // How much of the previous level carries over each time a new buffer of samples arrives, so
+    // the level follows the music rather than jumping about on every beat.
+    const SMOOTHING: f32 = 0.8;
+
+    pub struct Audio {
+        // Input stops when the stream is dropped
+        _stream: cpal::Stream,
+
+        // The smoothed RMS amplitude (0..1) as f32 bits, written from the audio thread
+        level: Arc<AtomicU32>,
+    }
+
+    fn build_stream<T>(
+        device: &cpal::Device,
+        config: &cpal::StreamConfig,
+        level: Arc<AtomicU32>,
+    ) -> Result<cpal::Stream, cpal::BuildStreamError>
+    where
+        T: SizedSample,
+        f32: FromSample<T>,
+    {
+        device.build_input_stream(
+            config,
+            move |data: &[T], _: &cpal::InputCallbackInfo| {
+                let sum: f32 = data
+                    .iter()
+                    .map(|&sample| {
+                        let sample: f32 = sample.to_sample();
+                        sample * sample
+                    })
+                    .sum();
+                let rms = (sum / data.len().max(1) as f32).sqrt();
+
+                let previous = f32::from_bits(level.load(Ordering::Relaxed));
+                let smoothed = previous * SMOOTHING + rms * (1.0 - SMOOTHING);
+                level.store(smoothed.to_bits(), Ordering::Relaxed);
+            },
+            |e| eprintln!("Audio input error: {}", e),
+            None,
+        )
+    }
+
+    impl Audio {
+        pub fn listen(quiet: bool) -> Option<Audio> {
+            let fall_back = |reason: String| {
+                if !quiet {
+                    eprintln!("{}, animating normally", reason);
+                }
+            };
+
+            let device = cpal::default_host().default_input_device();
+            let device = match device {
+                Some(device) => device,
+                None => {
+                    fall_back("No audio input device".to_string());
+                    return None;
+                }
+            };
+
+            let config = match device.default_input_config() {
+                Ok(config) => config,
+                Err(e) => {
+                    fall_back(format!("Can't read audio input config ({})", e));
+                    return None;
+                }
+            };
+
+            let level = Arc::new(AtomicU32::new(0.0_f32.to_bits()));
+            let stream_config = config.config();
+            let stream = match config.sample_format() {
+                SampleFormat::F32 => build_stream::<f32>(&device, &stream_config, level.clone()),
+                SampleFormat::I16 => build_stream::<i16>(&device, &stream_config, level.clone()),
+                SampleFormat::U16 => build_stream::<u16>(&device, &stream_config, level.clone()),
+                format => {
+                    fall_back(format!("Unsupported audio sample format {}", format));
+                    return None;
+                }
+            };
+
+            let stream = match stream.map_err(|e| e.to_string()).and_then(|stream| {
+                stream.play().map_err(|e| e.to_string())?;
+                Ok(stream)
+            }) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    fall_back(format!("Can't start audio input ({})", e));
+                    return None;
+                }
+            };
+
+            Some(Audio {
+                _stream: stream,
+                level,
+            })
+        }
+
+        pub fn level(&self) -> f32 {
+            f32::from_bits(self.level.load(Ordering::Relaxed)).min(1.0)
+        }
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+mod input {
+    pub struct Audio;
+
+    impl Audio {
+        pub fn listen(_quiet: bool) -> Option<Audio> {
+            None
+        }
+
+        pub fn level(&self) -> f32 {
+            0.0
+        }
+    }
+}
+
+pub use input::Audio;
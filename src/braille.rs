@@ -0,0 +1,202 @@
+// Unicode Braille patterns pack a 2x4 grid of dots into a single character, starting at U+2800.
+// Each dot sets one bit of the codepoint offset:
+//
+//   1 4    0x01 0x08
+//   2 5    0x02 0x10
+//   3 6    0x04 0x20
+//   7 8    0x40 0x80
+use std::fmt::Write;
+
+const BRAILLE_BASE: u32 = 0x2800;
+
+const CELL_WIDTH: usize = 2;
+const CELL_HEIGHT: usize = 4;
+
+#[rustfmt::skip]
+const DOT_BITS: [[u32; CELL_WIDTH]; CELL_HEIGHT] = [
+    [0x01, 0x08],
+    [0x02, 0x10],
+    [0x04, 0x20],
+    [0x40, 0x80],
+];
+
+// How far a pixel has to be from the background in any of its channels, out of 255, to raise a dot.
+// Enough that film grain over the background doesn't, but less than the gradient's darkest color
+// differs from black.
+const DOT_THRESHOLD: u32 = 12;
+
+// Whether the donut was drawn over `pixel`, with `background` being what was there before
+fn is_dot(pixel: u32, background: u32) -> bool {
+    [16, 8, 0].iter().any(|&shift| {
+        let (a, b) = ((pixel >> shift) & 0xff, (background >> shift) & 0xff);
+        a.abs_diff(b) > DOT_THRESHOLD
+    })
+}
+
+// Convert a rendered frame into rows of Braille characters. Any pixel the donut was drawn over,
+// which is to say any which stands out from the background it was drawn on, becomes a raised dot.
+//
+// When the width or height isn't a multiple of the cell size, the last column/row of characters
+// only covers part of a cell and the missing dots are left unset.
+//
+// `char_aspect` is how many times taller than wide the terminal's characters are. At 2 (most
+// terminals) the dots are square and each row of dots is a row of the frame, otherwise rows of the
+// frame are skipped or repeated so the donut stays round.
+pub fn frame_to_braille(
+    output: &[u32],
+    background: &[u32],
+    width: usize,
+    height: usize,
+    char_aspect: f32,
+) -> String {
+    // How many rows of the frame each row of dots covers
+    let stretch = char_aspect * CELL_WIDTH as f32 / CELL_HEIGHT as f32;
+    let dot_rows = (height as f32 / stretch).ceil() as usize;
+
+    let columns = width.div_ceil(CELL_WIDTH);
+    let rows = dot_rows.div_ceil(CELL_HEIGHT);
+
+    let mut s = String::with_capacity((columns * 3 + 1) * rows);
+
+    for row in 0..rows {
+        for column in 0..columns {
+            let mut bits = 0;
+
+            for (dy, row_bits) in DOT_BITS.iter().enumerate() {
+                for (dx, bit) in row_bits.iter().enumerate() {
+                    let x = column * CELL_WIDTH + dx;
+                    let dot_row = row * CELL_HEIGHT + dy;
+                    let y = (dot_row as f32 * stretch) as usize;
+
+                    let inside = x < width && dot_row < dot_rows && y < height;
+                    if inside && is_dot(output[y * width + x], background[y * width + x]) {
+                        bits |= bit;
+                    }
+                }
+            }
+
+            s.push(std::char::from_u32(BRAILLE_BASE + bits).unwrap());
+        }
+
+        s.push('\n');
+    }
+
+    s
+}
+
+// A box of the terminal to draw into, leaving everything outside it alone. `column` and `row` are
+// where its top-left corner is, counting from 1 like the terminal's cursor positions do.
+#[derive(Clone, Copy)]
+pub struct Region {
+    pub column: usize,
+    pub row: usize,
+    pub columns: usize,
+    pub rows: usize,
+}
+
+// Like `frame_to_braille`, but shrunk or grown to fit inside `region` (staying round) and with each
+// row of characters moved into place with a cursor position escape, instead of separated by
+// newlines. Space fills the rest of the region, so nothing from before shows through.
+pub fn frame_to_braille_region(
+    output: &[u32],
+    background: &[u32],
+    width: usize,
+    height: usize,
+    char_aspect: f32,
+    region: &Region,
+) -> String {
+    // The most frame pixels the region has room for across and down
+    let stretch = char_aspect * CELL_WIDTH as f32 / CELL_HEIGHT as f32;
+    let scale = ((region.columns * CELL_WIDTH) as f32 / width as f32)
+        .min((region.rows * CELL_HEIGHT) as f32 * stretch / height as f32);
+    let fit_width = ((width as f32 * scale) as usize).max(1);
+    let fit_height = ((height as f32 * scale) as usize).max(1);
+
+    let fit = |pixels: &[u32]| {
+        let mut fitted = Vec::with_capacity(fit_width * fit_height);
+        for y in 0..fit_height {
+            let row = y * height / fit_height * width;
+            fitted.extend((0..fit_width).map(|x| pixels[row + x * width / fit_width]));
+        }
+        fitted
+    };
+
+    let text = frame_to_braille(
+        &fit(output),
+        &fit(background),
+        fit_width,
+        fit_height,
+        char_aspect,
+    );
+    let mut lines = text.lines();
+
+    let mut s = String::with_capacity((region.columns * 3 + 8) * region.rows);
+    for row in 0..region.rows {
+        write!(s, "\x1b[{};{}H", region.row + row, region.column).unwrap();
+
+        let line = lines.next().unwrap_or("");
+        let mut written = 0;
+        for c in line.chars().take(region.columns) {
+            s.push(c);
+            written += 1;
+        }
+        s.extend(std::iter::repeat_n(' ', region.columns - written));
+    }
+
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn background_raises_no_dots() {
+        let background = [0x808080, 0x808080, 0xffffff, 0x000000];
+        let output = [0x808080, 0x848484, 0x000000, 0x19071a];
+        let text = frame_to_braille(&output, &background, 2, 2, 2.0);
+
+        // Only the bottom row of the frame stands out from what was there before, dots 2 and 5
+        assert_eq!(text, "\u{2812}\n");
+    }
+
+    #[test]
+    fn region_rows_start_with_cursor_positions() {
+        let region = Region {
+            column: 5,
+            row: 3,
+            columns: 4,
+            rows: 2,
+        };
+        let text = frame_to_braille_region(&[0xffffff; 64], &[0; 64], 8, 8, 2.0, &region);
+
+        let rows: Vec<&str> = text.split("\x1b[").skip(1).collect();
+        assert!(text.starts_with("\x1b["));
+        assert_eq!(rows.len(), 2);
+        for (i, row) in rows.iter().enumerate() {
+            let (position, cells) = row.split_at(row.find('H').unwrap() + 1);
+            assert_eq!(position, format!("{};5H", 3 + i));
+            assert_eq!(cells.chars().count(), 4);
+        }
+    }
+
+    #[test]
+    fn char_aspect_maps_dot_rows_to_frame_rows() {
+        // Only the fifth row of a 2x8 frame stands out
+        let mut output = [0; 16];
+        output[8..10].copy_from_slice(&[0xffffff; 2]);
+
+        // Square dots: a dot for every row, so two characters, the second with its top dots raised
+        let text = frame_to_braille(&output, &[0; 16], 2, 8, 2.0);
+        assert_eq!(text, "\u{2800}\n\u{2809}\n");
+
+        // Characters twice as tall again: a dot for every other row, so one character with its
+        // third row of dots raised
+        let text = frame_to_braille(&output, &[0; 16], 2, 8, 4.0);
+        assert_eq!(text, "\u{2824}\n");
+
+        // Squatter characters: each row is repeated over two rows of dots
+        let text = frame_to_braille(&output, &[0; 16], 2, 8, 1.0);
+        assert_eq!(text, "\u{2800}\n\u{2800}\n\u{281b}\n\u{2800}\n");
+    }
+}
@@ -0,0 +1,249 @@
+// Draw straight to a Linux framebuffer device like /dev/fb0, for the `fbdev` feature, with no
+// window system needed. Frames are scaled up by the largest whole number which fits, centered, and
+// converted to the device's own pixel layout. Without the feature, asking for a framebuffer is an
+// error.
+
+// Pack a 0xRRGGBB color into a device pixel with the red, green, and blue channels at these
+// (offset, length) in bits, keeping the top `length` bits of each
+#[cfg(any(feature = "fbdev", test))]
+fn pack(color: u32, [red, green, blue]: [(u32, u32); 3]) -> u32 {
+    let channel = |value: u32, (offset, length): (u32, u32)| {
+        ((value & 0xff) >> (8 - length.min(8))) << offset
+    };
+
+    channel(color >> 16, red) | channel(color >> 8, green) | channel(color, blue)
+}
+
+// Where the visible screen is in the device's memory and how its pixels are laid out: rows are
+// `line_length` bytes apart, the screen starts `xoffset` pixels across and `yoffset` rows down
+// them, and each pixel takes `bytes` bytes with the channels at these (offset, length) in bits
+#[cfg(any(feature = "fbdev", test))]
+struct Layout {
+    width: usize,
+    height: usize,
+    xoffset: usize,
+    yoffset: usize,
+    line_length: usize,
+    bytes: usize,
+    channels: [(u32, u32); 3],
+}
+
+#[cfg(any(feature = "fbdev", test))]
+impl Layout {
+    // Where the first row of the visible screen starts, in bytes
+    fn start(&self) -> u64 {
+        (self.yoffset * self.line_length) as u64
+    }
+
+    // Draw a `width * height` frame into `frame`, the visible screen's rows from `start` on, as
+    // big as it fits
+    fn draw(&self, buffer: &[u32], width: usize, height: usize, frame: &mut [u8]) {
+        let scale = (self.width / width).min(self.height / height).max(1);
+        let left = self.xoffset + self.width.saturating_sub(width * scale) / 2;
+        let top = self.height.saturating_sub(height * scale) / 2;
+
+        for y in 0..(height * scale).min(self.height) {
+            let row = &mut frame[(top + y) * self.line_length..];
+            for x in 0..(width * scale).min(self.width) {
+                let pixel = pack(buffer[y / scale * width + x / scale], self.channels);
+                let start = (left + x) * self.bytes;
+                row[start..start + self.bytes].copy_from_slice(&pixel.to_le_bytes()[..self.bytes]);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "fbdev")]
+mod device {
+    use crate::target::RenderTarget;
+    use std::io::{Seek, SeekFrom, Write};
+    use std::os::unix::io::AsRawFd;
+
+    // From linux/fb.h
+    const FBIOGET_VSCREENINFO: libc::c_ulong = 0x4600;
+    const FBIOGET_FSCREENINFO: libc::c_ulong = 0x4602;
+
+    // The kernel fills these in, and only some of the fields matter here
+    #[allow(dead_code)]
+    #[repr(C)]
+    #[derive(Default)]
+    struct Bitfield {
+        offset: u32,
+        length: u32,
+        msb_right: u32,
+    }
+
+    #[allow(dead_code)]
+    #[repr(C)]
+    #[derive(Default)]
+    struct VarScreenInfo {
+        xres: u32,
+        yres: u32,
+        xres_virtual: u32,
+        yres_virtual: u32,
+        xoffset: u32,
+        yoffset: u32,
+        bits_per_pixel: u32,
+        grayscale: u32,
+        red: Bitfield,
+        green: Bitfield,
+        blue: Bitfield,
+        transp: Bitfield,
+        nonstd: u32,
+        activate: u32,
+        height: u32,
+        width: u32,
+        accel_flags: u32,
+        timings: [u32; 11],
+        reserved: [u32; 4],
+    }
+
+    #[allow(dead_code)]
+    #[repr(C)]
+    #[derive(Default)]
+    struct FixScreenInfo {
+        id: [u8; 16],
+        smem_start: libc::c_ulong,
+        smem_len: u32,
+        kind: u32,
+        type_aux: u32,
+        visual: u32,
+        xpanstep: u16,
+        ypanstep: u16,
+        ywrapstep: u16,
+        line_length: u32,
+        mmio_start: libc::c_ulong,
+        mmio_len: u32,
+        accel: u32,
+        capabilities: u16,
+        reserved: [u16; 2],
+    }
+
+    pub struct FbdevTarget {
+        file: std::fs::File,
+        layout: super::Layout,
+        frame: Vec<u8>,
+        limit_rate: bool,
+    }
+
+    fn fail(path: &str, e: impl std::fmt::Display) -> ! {
+        panic!("{}: {}", path, e);
+    }
+
+    impl FbdevTarget {
+        pub fn open(path: &str, limit_rate: bool) -> FbdevTarget {
+            let file = std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(path)
+                .unwrap_or_else(|e| fail(path, e));
+
+            let mut var = VarScreenInfo::default();
+            let mut fix = FixScreenInfo::default();
+            // SAFETY: both ioctls only write into the struct they're given, which is laid out to
+            // match the kernel's.
+            unsafe {
+                if libc::ioctl(file.as_raw_fd(), FBIOGET_VSCREENINFO, &mut var) != 0
+                    || libc::ioctl(file.as_raw_fd(), FBIOGET_FSCREENINFO, &mut fix) != 0
+                {
+                    fail(path, std::io::Error::last_os_error());
+                }
+            }
+
+            let bytes = match var.bits_per_pixel {
+                16 => 2,
+                32 => 4,
+                bits => fail(path, format!("{} bits per pixel isn't supported", bits)),
+            };
+            // The visible screen can be panned across a bigger virtual one
+            let layout = super::Layout {
+                width: var.xres as usize,
+                height: var.yres as usize,
+                xoffset: var.xoffset as usize,
+                yoffset: var.yoffset as usize,
+                line_length: fix.line_length as usize,
+                bytes,
+                channels: [&var.red, &var.green, &var.blue].map(|c| (c.offset, c.length)),
+            };
+
+            FbdevTarget {
+                file,
+                frame: vec![0; layout.line_length * layout.height],
+                layout,
+                limit_rate,
+            }
+        }
+    }
+
+    impl RenderTarget for FbdevTarget {
+        fn present(&mut self, buffer: &[u32], width: usize, height: usize) {
+            self.layout.draw(buffer, width, height, &mut self.frame);
+
+            self.file
+                .seek(SeekFrom::Start(self.layout.start()))
+                .unwrap();
+            self.file.write_all(&self.frame).unwrap();
+
+            if self.limit_rate {
+                std::thread::sleep(crate::target::FRAME_TIME);
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "fbdev"))]
+mod device {
+    use crate::target::RenderTarget;
+
+    pub struct FbdevTarget;
+
+    impl FbdevTarget {
+        pub fn open(_path: &str, _limit_rate: bool) -> FbdevTarget {
+            eprintln!("--fbdev needs donut to be built with the fbdev feature");
+            std::process::exit(1);
+        }
+    }
+
+    impl RenderTarget for FbdevTarget {
+        fn present(&mut self, _buffer: &[u32], _width: usize, _height: usize) {}
+    }
+}
+
+pub use device::FbdevTarget;
+
+#[cfg(test)]
+mod tests {
+    // Where the channels go in a 16-bit RGB565 framebuffer
+    const RGB565: [(u32, u32); 3] = [(11, 5), (5, 6), (0, 5)];
+
+    #[test]
+    fn packs_rgb565() {
+        assert_eq!(super::pack(0xff0000, RGB565), 0xf800);
+        assert_eq!(super::pack(0x00ff00, RGB565), 0x07e0);
+        assert_eq!(super::pack(0x0000ff, RGB565), 0x001f);
+        assert_eq!(super::pack(0xffffff, RGB565), 0xffff);
+        assert_eq!(super::pack(0x000000, RGB565), 0x0000);
+        assert_eq!(super::pack(0x808080, RGB565), 0x8410);
+    }
+
+    #[test]
+    fn draws_at_the_panned_offset_within_padded_rows() {
+        let layout = super::Layout {
+            width: 4,
+            height: 2,
+            xoffset: 1,
+            yoffset: 3,
+            line_length: 12,
+            bytes: 2,
+            channels: RGB565,
+        };
+        assert_eq!(layout.start(), 3 * 12);
+
+        // Scaled up 2x, starting a pixel in on each row and leaving the padding past the screen
+        let mut frame = vec![0; 12 * 2];
+        layout.draw(&[0xff0000, 0x0000ff], 2, 1, &mut frame);
+        let row = [0, 0, 0x00, 0xf8, 0x00, 0xf8, 0x1f, 0x00, 0x1f, 0x00, 0, 0];
+        assert_eq!(frame[..12], row);
+        assert_eq!(frame[12..], row);
+    }
+}
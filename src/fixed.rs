@@ -0,0 +1,88 @@
+// Fixed-point replacements for the parts of the renderer that can give different results on
+// different platforms, enabled with the `fixed-point` feature.
+//
+// Basic f32 arithmetic is IEEE 754 and rounds the same everywhere, but `sin` and `cos` come from
+// the platform's libm and can differ in the last bit, which is enough to move a sample across a
+// pixel boundary. Here they're computed with integer arithmetic instead, and the perspective divide
+// and projection are done in Q16.16 fixed point so the final pixel coordinates are exact.
+//
+// The trade-offs:
+//
+// - Trig is evaluated in Q2.30 with a 9th order Taylor series, accurate to about 4e-6, which is
+//   well below anything visible but means frames don't exactly match the floating-point renderer.
+// - Angles, coordinates and 1/z are rounded to 1/65536 before projecting, so depths closer together
+//   than that compare equal and the first sample plotted wins.
+// - The projection multiplies in i64, which only overflows for points far off screen and clamped
+//   right up against a tiny `RenderConfig::z_near`. Depths under 1/65536 are taken as 1/65536.
+// - Only the trig and projection are replaced. `powf` for `RenderConfig::shading_gamma`, `acos`
+//   for the sparkle, and `exp` for the falloff of splats and bloom still come from libm, so frames
+//   using those can still differ in the last bit between platforms.
+
+const FRACTION_BITS: u32 = 16;
+const ONE: i64 = 1 << FRACTION_BITS;
+
+// Higher precision used while evaluating the series
+const TRIG_BITS: u32 = 30;
+
+fn to_fixed(value: f32) -> i64 {
+    (value * ONE as f32).round() as i64
+}
+
+fn from_fixed(value: i64) -> f32 {
+    value as f32 / ONE as f32
+}
+
+fn trig_mul(a: i64, b: i64) -> i64 {
+    (a * b) >> TRIG_BITS
+}
+
+// 2π, π and π/2 in Q16.16
+const TWO_PI: i64 = 411_775;
+const PI: i64 = 205_887;
+const HALF_PI: i64 = 102_944;
+
+// sin(x) for x in Q16.16
+fn sin(x: i64) -> i64 {
+    // Reduce to -π..π, then fold into -π/2..π/2 where the series converges quickly, using
+    // sin(π - x) = sin(x).
+    let mut x = x.rem_euclid(TWO_PI);
+    if x >= PI {
+        x -= TWO_PI;
+    }
+    if x > HALF_PI {
+        x = PI - x;
+    } else if x < -HALF_PI {
+        x = -PI - x;
+    }
+
+    // x - x^3/3! + x^5/5! - x^7/7! + x^9/9!
+    let x = x << (TRIG_BITS - FRACTION_BITS);
+    let x2 = trig_mul(x, x);
+    let mut term = x;
+    let mut sum = x;
+    for n in [2 * 3, 4 * 5, 6 * 7, 8 * 9] {
+        term = -trig_mul(term, x2) / n;
+        sum += term;
+    }
+
+    sum >> (TRIG_BITS - FRACTION_BITS)
+}
+
+/// The sine and cosine of `angle`, as the renderer works them out: in integer arithmetic, so
+/// they're the same on every platform.
+pub fn sin_cos(angle: f32) -> (f32, f32) {
+    let angle = to_fixed(angle);
+    (from_fixed(sin(angle)), from_fixed(sin(angle + HALF_PI)))
+}
+
+pub fn project(x: f32, y: f32, z: f32, k1: f32, width: usize, height: usize) -> (f32, f32, f32) {
+    let (x, y, z, k1) = (to_fixed(x), to_fixed(y), to_fixed(z), to_fixed(k1));
+
+    let ooz = (ONE << FRACTION_BITS) / z.max(1);
+    let scale = (k1 * ooz) >> FRACTION_BITS;
+
+    let xp = ((width as i64) << (FRACTION_BITS - 1)) + ((scale * x) >> FRACTION_BITS);
+    let yp = ((height as i64) << (FRACTION_BITS - 1)) - ((scale * y) >> FRACTION_BITS);
+
+    (from_fixed(ooz), from_fixed(xp), from_fixed(yp))
+}
@@ -0,0 +1,4134 @@
+// https://www.a1k0n.net/2011/07/20/donut-math.html
+
+#[cfg(any(feature = "fixed-point", test))]
+mod fixed;
+pub mod post;
+
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+fn xy(width: usize, x: usize, y: usize) -> usize {
+    y * width + x
+}
+
+const GRADIENT: [u32; 12] = [
+    // 0x421e0f, // Brown 3
+    0x19071a, // Dark violett
+    0x09012f, // Darkest blue
+    0x040449, // Blue 5
+    0x000764, // Blue 4
+    // 0x0c2c8a, // Blue 3
+    // 0x1852b1, // Blue 2
+    0x397dd1, // Blue 1
+    0x86b5e5, // Blue 0
+    0xd3ecf8, // Lightest blue
+    0xf1e9bf, // Lightest yellow
+    0xf8c95f, // Light yellow
+    0xffaa00, // Dirty yellow
+    0xcc8000, // Brown 0
+    // 0x995700, // Brown 1
+    0x6a3403, // Brown 2
+];
+
+/// The colors donuts are shaded with unless `RenderConfig::palette` says otherwise, from the least
+/// lit surface to the most: brightening through the blues to the lightest blue and yellow, then
+/// deepening through yellow to brown where the light hits head on.
+pub fn default_gradient() -> &'static [u32] {
+    &GRADIENT
+}
+
+// How far apart samples are around the tube and around the ring, in radians, by default
+const THETA_SPACING: f32 = 0.007;
+const PHI_SPACING: f32 = 0.002;
+
+// How much `auto_tune_spacing` changes the spacing by each step, and the coarsest it'll go
+const TUNE_STEP: f32 = 1.1;
+const TUNE_LIMIT: f32 = 0.5;
+
+// Inner torus radius, by default
+const R1: f32 = 1.0;
+
+// Outer torus radius, by default
+const R2: f32 = 2.0;
+
+// The distance of the donut from the viewer
+const K2: f32 = 5.0;
+
+// The closest a surface point can be to the viewer by default, see `RenderConfig::z_near`
+const Z_NEAR: f32 = 0.1;
+
+// Calculate K1 based on screen size: The maximum x-distance occurs roughly at the edge of the
+// torus, which is at x=R1+R2, z=0.
+//
+// We want that to be displaced 3/8ths of the width of the screen, which is 3/4th of the way from
+// the center to the side of the screen.
+//
+// screen_width * 3/8 = K1 * (R1 + R2) / (K2 + 0)
+// screen_width * K2 *3 / (8 * (R1 + R2)) = K1
+//
+// The radii can be changed, so the donut is always scaled to fit. With auto-framing, K1 is instead
+// whatever makes the box from `projected_bounds` fit the screen with the margin to spare, as long
+// as the viewer is outside the donut's bounding sphere.
+fn k1(config: &RenderConfig) -> f32 {
+    if let (Some(margin), Some(slope)) = (config.auto_frame, bounding_slope(config)) {
+        let half = config.width.min(config.height) as f32 / 2.0;
+        return ((half - margin) / slope).max(0.0);
+    }
+
+    config.width as f32 * K2 * 3.0 / (8.0 * (config.r1 + config.r2))
+}
+
+// Sines and cosines, and the perspective projection, go through these so the `fixed-point` feature
+// can swap in implementations which give the same results on every platform.
+#[cfg(feature = "fixed-point")]
+use fixed::project;
+#[cfg(feature = "fixed-point")]
+pub use fixed::sin_cos;
+#[cfg(not(feature = "fixed-point"))]
+use float::project;
+#[cfg(not(feature = "fixed-point"))]
+pub use float::sin_cos;
+
+#[cfg(any(not(feature = "fixed-point"), test))]
+mod float {
+    /// The sine and cosine of `angle`, as the renderer works them out: from the platform's libm,
+    /// unless the `fixed-point` feature swaps in its own.
+    pub fn sin_cos(angle: f32) -> (f32, f32) {
+        (angle.sin(), angle.cos())
+    }
+
+    // Project a point onto the screen, returning 1/z along with the x and y screen coordinates
+    pub fn project(
+        x: f32,
+        y: f32,
+        z: f32,
+        k1: f32,
+        width: usize,
+        height: usize,
+    ) -> (f32, f32, f32) {
+        let ooz = 1.0 / z; // "one over z"
+
+        // Note that y is negated here, because y goes up in 3D space but down on 2D displays.
+        let xp = width as f32 / 2.0 + k1 * ooz * x;
+        let yp = height as f32 / 2.0 - k1 * ooz * y;
+
+        (ooz, xp, yp)
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// Linearly interpolate each 0xRRGGBB channel from `from` to `to`
+fn lerp_color(from: u32, to: u32, t: f32) -> u32 {
+    let channel = |shift: u32| {
+        let from = ((from >> shift) & 0xff) as f32;
+        let to = ((to >> shift) & 0xff) as f32;
+        ((from + (to - from) * t).round() as u32) << shift
+    };
+
+    channel(16) | channel(8) | channel(0)
+}
+
+// Fully melted, how far points are dragged down: the square of how far below the middle they are
+// times MELT_DEPTH, give or take MELT_DRIP of that, varying MELT_DRIP_FREQUENCY times a world unit
+// across.
+const MELT_DEPTH: f32 = 0.15;
+const MELT_DRIP: f32 = 0.5;
+const MELT_DRIP_FREQUENCY: f32 = 5.0;
+
+// Grow a hair from every this many samples of the torus in each direction, giving around 60 each
+// way round.
+const FUR_THETA_STEPS: usize = 15;
+const FUR_PHI_STEPS: usize = 50;
+
+// Roughly how far apart samples are on surfaces other than the torus, in world units
+const CYLINDER_SPACING: f32 = 0.006;
+
+/// What to render. With the default radii, every shape fits inside the sphere the donut does, so
+/// the framing stays the same whichever is picked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Torus,
+
+    /// A cylinder standing along the y-axis, capped at both ends.
+    Cylinder {
+        radius: f32,
+        height: f32,
+    },
+}
+
+impl std::str::FromStr for Shape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Shape, String> {
+        match s {
+            "torus" => Ok(Shape::Torus),
+            "cylinder" => Ok(Shape::Cylinder {
+                radius: 2.0,
+                height: 2.0,
+            }),
+            _ => Err(format!("unknown shape: {}", s)),
+        }
+    }
+}
+
+// The light shines from above and behind the viewer, (0, 1, -1), normalized
+const LIGHT: [f32; 3] = [
+    0.0,
+    std::f32::consts::FRAC_1_SQRT_2,
+    -std::f32::consts::FRAC_1_SQRT_2,
+];
+
+// `RenderConfig::sparkle` lights up the surface within this many radians of facing straight along
+// `sparkle_direction`, and `sparkle_direction` circles round the front of the donut this many
+// radians a frame, leaning this far out from straight towards the viewer.
+const SPARKLE_SIZE: f32 = 0.15;
+const SPARKLE_SPEED: f32 = 0.02;
+const SPARKLE_LEAN: f32 = 0.6;
+
+/// Where the glint from `RenderConfig::sparkle` is on frame `frame`: the direction a surface has
+/// to face to catch it, circling round in front of the donut as the frames go by.
+pub fn sparkle_direction(frame: u64) -> [f32; 3] {
+    let (sin, cos) = sin_cos(frame as f32 * SPARKLE_SPEED);
+    let [x, y, z] = [SPARKLE_LEAN * cos, SPARKLE_LEAN * sin, -1.0];
+    let length = (x * x + y * y + z * z).sqrt();
+    [x / length, y / length, z / length]
+}
+
+/// Where `light` is once the donut has turned by `a` and `b`, turning with it: the lighting of a
+/// still donut with the camera orbiting around it, rather than a donut spinning under a still
+/// light. `light` is where the light is with the donut unturned.
+pub fn orbit_light(light: [f32; 3], a: f32, b: f32) -> [f32; 3] {
+    rotate(light, sin_cos(a), sin_cos(b))
+}
+
+// The two surface colors used by the checkerboard
+const CHECKER_COLORS: [u32; 2] = [0xf8c95f, 0x397dd1];
+
+/// An image to wrap around the donut, as `width * height` 0xRRGGBB pixels.
+#[derive(Clone)]
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl Texture {
+    // Look up the texel at (u, v), where the texture spans 0..1 in each direction and repeats
+    // outside of that.
+    fn sample(&self, u: f32, v: f32) -> u32 {
+        let x = (u.rem_euclid(1.0) * self.width as f32) as usize;
+        let y = (v.rem_euclid(1.0) * self.height as f32) as usize;
+
+        // rem_euclid can round up to exactly 1.0 for tiny negative inputs
+        self.pixels[xy(self.width, x.min(self.width - 1), y.min(self.height - 1))]
+    }
+}
+
+/// A palette of `shades` colors going from black up to `color`, for a single hue donut.
+pub fn mono_palette(color: u32, shades: usize) -> Vec<u32> {
+    let last = shades.max(2) - 1;
+    (0..=last)
+        .map(|i| lerp_color(0, color, i as f32 / last as f32))
+        .collect()
+}
+
+// A number from 0 to 1 which is always the same for the same `seed` and `n`, but looks random
+fn seeded(seed: u64, n: u64) -> f32 {
+    // SplitMix64's finalizer, which mixes every input bit into every output bit
+    let mut x = seed ^ n.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^= x >> 31;
+    (x >> 40) as f32 / (1u64 << 24) as f32
+}
+
+// A color from its hue (0..1 around the color wheel), saturation, and value, all 0..1
+fn hsv(hue: f32, saturation: f32, value: f32) -> u32 {
+    let channel = |offset: f32| {
+        // The usual HSV to RGB formula, one channel at a time
+        let k = (offset + hue.rem_euclid(1.0) * 6.0) % 6.0;
+        let strength = (k.min(4.0 - k)).clamp(0.0, 1.0);
+        let c = value - value * saturation * strength;
+        (c.clamp(0.0, 1.0) * 255.0).round() as u32
+    };
+
+    channel(5.0) << 16 | channel(3.0) << 8 | channel(1.0)
+}
+
+// The `n`th of `proc_palette`'s waypoints: a dark to bright ramp around a seeded hue, drifting a
+// little way round the color wheel and losing saturation towards the top, so it ends near white.
+fn waypoint_palette(seed: u64, n: u64) -> Vec<u32> {
+    let hue = seeded(seed, 3 * n);
+    let drift = (seeded(seed, 3 * n + 1) - 0.5) * 0.3;
+    let saturation = 0.5 + 0.5 * seeded(seed, 3 * n + 2);
+
+    let last = GRADIENT.len() - 1;
+    (0..=last)
+        .map(|i| {
+            let t = i as f32 / last as f32;
+            hsv(
+                hue + drift * t,
+                saturation * (1.0 - 0.5 * t.powi(4)),
+                0.1 + 0.9 * t,
+            )
+        })
+        .collect()
+}
+
+/// A palette which drifts through a series of color schemes picked by `seed`, reaching the `n`th
+/// at `t` = `n` and easing smoothly from one to the next in between. The same `seed` and `t`
+/// always give the same palette, so small steps in `t` give small changes in color.
+pub fn proc_palette(seed: u64, t: f32) -> Vec<u32> {
+    let t = t.max(0.0);
+    let n = t as u64;
+    let blend = smoothstep(0.0, 1.0, t - n as f32);
+    blend_palettes(
+        &waypoint_palette(seed, n),
+        &waypoint_palette(seed, n + 1),
+        blend,
+    )
+}
+
+/// A smooth palette worked out from `a + b * cos(2π * (c * t + d))` for each of red, green, and
+/// blue, rather than picked from a list of colors. Each coefficient holds the red, green, and blue
+/// values, which are 0..1 wherever the formula lands in range.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CosinePalette {
+    pub a: [f32; 3],
+    pub b: [f32; 3],
+    pub c: [f32; 3],
+    pub d: [f32; 3],
+}
+
+impl CosinePalette {
+    /// The 0xRRGGBB color at `t`, 0 being the darkest end of the shading and 1 the brightest.
+    /// Channels outside 0..1 are clamped.
+    pub fn sample(&self, t: f32) -> u32 {
+        let channel = |i: usize| {
+            let (_, cos) = sin_cos(2.0 * std::f32::consts::PI * (self.c[i] * t + self.d[i]));
+            let value = self.a[i] + self.b[i] * cos;
+            (value.clamp(0.0, 1.0) * 255.0).round() as u32
+        };
+
+        channel(0) << 16 | channel(1) << 8 | channel(2)
+    }
+}
+
+/// The palette `t` of the way from `from` to `to`, blending each channel. It has as many colors as
+/// `to`, each blended with the color as far through `from`, so palettes of different lengths blend
+/// too and `t` of 1 gives exactly `to`.
+pub fn blend_palettes(from: &[u32], to: &[u32], t: f32) -> Vec<u32> {
+    to.iter()
+        .enumerate()
+        .map(|(i, &color)| lerp_color(from[i * from.len() / to.len()], color, t))
+        .collect()
+}
+
+/// How overlapping parts of the surface hide each other.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ZMode {
+    /// The nearest surface wins, using the z-buffer.
+    Depth,
+
+    /// The painter's algorithm: whatever is drawn last wins, near or far. Surfaces facing away
+    /// from the light are still skipped.
+    Painter,
+}
+
+impl std::str::FromStr for ZMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ZMode, String> {
+        match s {
+            "depth" => Ok(ZMode::Depth),
+            "painter" => Ok(ZMode::Painter),
+            _ => Err(format!("unknown z mode: {}", s)),
+        }
+    }
+}
+
+/// How many bits of each color channel survive `post::reduce_color_depth`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorDepth {
+    /// All 8 bits of each channel, leaving colors alone.
+    True,
+
+    /// 16-bit color: 5 bits of red, 6 of green, and 5 of blue.
+    Rgb565,
+
+    /// 8-bit color: 3 bits of red, 3 of green, and 2 of blue.
+    Rgb332,
+}
+
+impl ColorDepth {
+    /// The bits kept for red, green, and blue.
+    pub fn bits(self) -> [u32; 3] {
+        match self {
+            ColorDepth::True => [8, 8, 8],
+            ColorDepth::Rgb565 => [5, 6, 5],
+            ColorDepth::Rgb332 => [3, 3, 2],
+        }
+    }
+}
+
+impl std::str::FromStr for ColorDepth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ColorDepth, String> {
+        match s {
+            "true" => Ok(ColorDepth::True),
+            "rgb565" => Ok(ColorDepth::Rgb565),
+            "rgb332" => Ok(ColorDepth::Rgb332),
+            _ => Err(format!("unknown color depth: {}", s)),
+        }
+    }
+}
+
+/// How a ramp from 0 to 1 gets there over time, for `ease`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Easing {
+    /// At a steady rate.
+    Linear,
+
+    /// Starting slowly and speeding up.
+    EaseIn,
+
+    /// Starting quickly and slowing down.
+    EaseOut,
+
+    /// Slowly at both ends, quickest in the middle.
+    EaseInOut,
+
+    /// Reaching 1 early and bouncing off it a few times, each bounce smaller than the last.
+    Bounce,
+}
+
+impl std::str::FromStr for Easing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Easing, String> {
+        match s {
+            "linear" => Ok(Easing::Linear),
+            "ease-in" => Ok(Easing::EaseIn),
+            "ease-out" => Ok(Easing::EaseOut),
+            "ease-in-out" => Ok(Easing::EaseInOut),
+            "bounce" => Ok(Easing::Bounce),
+            _ => Err(format!("unknown easing: {}", s)),
+        }
+    }
+}
+
+/// How far along a ramp shaped like `kind` is once `t` of its time is up. `t` is clamped to 0..1,
+/// and every kind gives 0 at the start and 1 at the end.
+pub fn ease(t: f32, kind: Easing) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    match kind {
+        Easing::Linear => t,
+        Easing::EaseIn => t * t,
+        Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+        Easing::EaseInOut => {
+            if t < 0.5 {
+                2.0 * t * t
+            } else {
+                1.0 - 2.0 * (1.0 - t) * (1.0 - t)
+            }
+        }
+        Easing::Bounce => {
+            // Falling with gravity then bouncing on 1, each parabola shorter and lower than the
+            // last
+            const N: f32 = 7.5625;
+            const D: f32 = 2.75;
+            if t < 1.0 / D {
+                N * t * t
+            } else if t < 2.0 / D {
+                let t = t - 1.5 / D;
+                N * t * t + 0.75
+            } else if t < 2.5 / D {
+                let t = t - 2.25 / D;
+                N * t * t + 0.9375
+            } else {
+                let t = t - 2.625 / D;
+                N * t * t + 0.984375
+            }
+        }
+    }
+}
+
+/// Settings which can change from one frame to the next.
+#[derive(Clone)]
+pub struct RenderConfig {
+    pub width: usize,
+    pub height: usize,
+
+    /// The radius of the donut's tube, and the distance from the center of the donut to the
+    /// middle of the tube. The donut is scaled so it always fits the screen the same way.
+    pub r1: f32,
+    pub r2: f32,
+
+    /// Unit vector pointing towards the light, in view space (+y up, +z away from the viewer).
+    pub light: [f32; 3],
+
+    /// Only plot the surface where its luminance (-1 facing directly away from the light, up to 1
+    /// facing it) is above this. 0 plots everything lit, higher only the brightest parts, and
+    /// below 0 some of the surface in shadow too, in the darkest shade.
+    pub cull_threshold: f32,
+
+    /// Don't plot the surface where its luminance is within this much of 0, where it's turning
+    /// from lit to shadowed and the darkest shade comes out speckled. 0 plots all of it.
+    pub terminator_deadzone: f32,
+
+    /// Brighten the surface by up to this much (0 for no sparkle) where it faces close to
+    /// `sparkle_direction`, for a glint which moves over the surface when `sparkle_direction`
+    /// follows `sparkle_direction(frame)`.
+    pub sparkle: f32,
+    pub sparkle_direction: [f32; 3],
+
+    /// Color the surface with a checkerboard this many squares around the tube, instead of the
+    /// gradient.
+    pub checker: Option<usize>,
+
+    /// Wrap an image around the surface, instead of the gradient. The horizontal axis goes around
+    /// the tube and the vertical axis around the center of the donut.
+    pub texture: Option<Texture>,
+
+    /// Split the tube into this many bands around its cross-section...
+    pub explode_segments: usize,
+
+    /// ...and push each band this far out from the middle of the tube, along its own center.
+    pub explode_distance: f32,
+
+    /// How strong the film grain added by `post::grain` is, 0 for none.
+    pub grain: f32,
+
+    /// Pixels brighter than this (0..1) glow when `post::bloom` is applied...
+    pub bloom_threshold: f32,
+
+    /// ...by this much, 0 for no bloom.
+    pub bloom_strength: f32,
+
+    /// Glow in this color around the outside of the donut when `post::outline_glow` is applied,
+    /// reaching about `outline_glow_radius` pixels out and `outline_glow_intensity` (0..1) of the
+    /// way to the color next to the donut.
+    pub outline_glow: Option<u32>,
+    pub outline_glow_radius: usize,
+    pub outline_glow_intensity: f32,
+
+    /// Raise luminance (0..1) to this power before picking a shade, 1 for linear shading.
+    pub shading_gamma: f32,
+
+    /// Stretch the luminance between these two (after `shading_gamma`) out to 0..1 before
+    /// picking a shade, so anything at or below `lum_floor` gets the darkest and anything at or
+    /// above `lum_ceil` the brightest. A narrower window gives more contrast.
+    pub lum_floor: f32,
+    pub lum_ceil: f32,
+
+    /// How many colors further along the palette to shade with than the lighting says, wrapping
+    /// around past the end. Changing it over time makes the shading shimmer across the surface.
+    pub gradient_scroll: f32,
+
+    /// Draw each sample as a soft blob reaching this many pixels out, blended with its neighbours,
+    /// for a smooth, out of focus look. Slower, the bigger the blobs. Must be above 0.
+    pub splat: Option<f32>,
+
+    /// Shade in this many flat bands, outlined in `CEL_OUTLINE` where they meet each other or the
+    /// background, rather than smoothly.
+    pub cel: Option<usize>,
+
+    /// Stretch the donut by these factors along the screen's x, y, and z axes, with 1 for its
+    /// usual size. Squashing along one and stretching along another makes an egg.
+    pub scale: [f32; 3],
+
+    /// How bright the donut's reflection is in a mirror it sits on, from 0 (no reflection) to 1.
+    /// The mirror is level with the screen, and the further from it, the more the reflection fades.
+    pub reflection: f32,
+
+    /// Draw the donut's x, y, and z axes in red, green, and blue, and its bounding box in white,
+    /// hidden wherever the donut is in front of them.
+    pub debug_axes: bool,
+
+    /// With several donuts (`render_row` and `render_ring`), treat each one as this much nearer
+    /// in 1/z than the one before it when testing the z-buffer, so where surfaces meet the later
+    /// donut consistently wins (or the earlier one, if negative). 0 leaves it to the exact depths,
+    /// which can flicker between the two.
+    pub z_bias: f32,
+
+    /// When a sample's 1/z is within this much of what's already in the z-buffer, blend the two
+    /// colors, favouring the nearer, instead of letting the nearer one win outright. This softens
+    /// the speckle where neighbouring samples fight over a pixel at grazing angles. 0 turns
+    /// blending off.
+    pub z_blend_epsilon: Depth,
+
+    /// Z-test the surface at this many times the output's resolution in each direction, then give
+    /// each pixel the color of the nearest of its finer pixels. 1 tests at the output's
+    /// resolution. Samples are drawn as single points while it's above 1, whatever `point_size`
+    /// says, so the edges of parts in front (like the rim) don't spread out over what's behind
+    /// them the way bigger points make them.
+    pub z_supersample: usize,
+
+    /// Outline the donut's edges, and wherever one part of it passes in front of another, with
+    /// smooth dark lines. The edges are found in a z-buffer rendered at twice the resolution.
+    pub crisp_outline: bool,
+
+    /// Treat the torus as a hollow shell this thick, in world units, darkening it towards the rim
+    /// where the wall is seen edge-on. From 0 (solid, the default) up to `r1`.
+    pub shell_thickness: f32,
+
+    /// Draw a coarse wireframe of the torus over it in this color, like a cage around it. Lines
+    /// behind the donut are hidden.
+    pub cage: Option<u32>,
+
+    /// Only render this part of the screen, x, y, width, and height in pixels, stretched out to
+    /// fill the whole output, to zoom in on it. Sample more closely (`theta_spacing` and
+    /// `phi_spacing`) to go with it, or the samples spread apart and leave gaps.
+    pub crop: Option<[f32; 4]>,
+
+    /// Instead of shading the donut, color each pixel by how many lit samples land on it, from
+    /// blue (the fewest) to red (the most), to show where the sampling is dense or sparse.
+    pub debug_density: bool,
+
+    /// Render as usual, then color each pixel the surface was drawn over more than once by how many
+    /// times, from blue to red like `debug_density`, to show where the z-buffer test does the
+    /// most work.
+    pub debug_overdraw: bool,
+
+    /// Grow hairs this long, in world units, out of the torus. They're picked out in the palette
+    /// by how near they are rather than by the light.
+    pub fur: Option<f32>,
+
+    /// How many colors `post::reduce_color_depth` leaves.
+    pub color_depth: ColorDepth,
+
+    /// How much `post::vignette` darkens the corners, 0 for none and 1 for black.
+    pub vignette: f32,
+
+    /// How much `post::scanlines` darkens every other row, 0 for none and 1 for black.
+    pub scanlines: f32,
+
+    /// The closest a surface point can be to the viewer, in world units. Anything nearer (or
+    /// behind the viewer) is clamped to this distance, which keeps 1/z from blowing up if the
+    /// donut reaches the camera. Must be above 0.
+    pub z_near: f32,
+
+    /// How far apart samples are around the tube (theta) and around the ring (phi) of the torus,
+    /// in radians. Closer together is slower but leaves fewer gaps, see [`check_sampling_density`].
+    pub theta_spacing: f32,
+    pub phi_spacing: f32,
+
+    /// Draw each sample as a disc this many pixels across rather than a single pixel, which
+    /// gives a stippled look.
+    pub point_size: usize,
+
+    /// Fade the donut in from the background over this many frames with `post::fade`, 0 to have
+    /// it appear straight away.
+    pub fade_in_frames: u64,
+
+    /// Wipe the donut in from the top over this many frames with `post::reveal`, 0 to have it
+    /// appear straight away.
+    pub reveal_frames: u64,
+
+    /// Only plot rows with the same parity as `interlace_field` (0 for even, 1 for odd), leaving
+    /// the others as they were.
+    pub interlace: bool,
+    pub interlace_field: usize,
+
+    /// The shape to render. Exploding only applies to the torus.
+    pub shape: Shape,
+
+    /// Move everything this many pixels right and down after projecting it.
+    pub screen_offset: [f32; 2],
+
+    /// Have `render_frame` draw a red-cyan anaglyph, the left eye's view in red and the right
+    /// eye's in green and blue, from eyes this far apart in world units (the donut is 6 across).
+    pub anaglyph: Option<f32>,
+
+    /// Scale the donut so it always fits on screen, however it's shaped, with at least this many
+    /// pixels to spare at the nearest edges.
+    pub auto_frame: Option<f32>,
+
+    /// Only draw the outline of the donut, in this color, leaving the inside showing whatever
+    /// was behind it.
+    pub silhouette: Option<u32>,
+
+    /// How overlapping parts of the surface hide each other.
+    pub z_mode: ZMode,
+
+    /// How far the bottom of the donut has melted and dripped down, from 0 (not at all) to 1. In a
+    /// row or ring, each donut melts below its own center.
+    pub melt: f32,
+
+    /// Accelerate from rest up to full speed over this many frames, 0 to start at full speed.
+    pub spin_up_frames: u64,
+
+    /// The shape of the ramps which play out over several frames: fading in, revealing, spinning
+    /// up, melting, crossfading palettes, and shakes dying down.
+    pub easing: Easing,
+
+    /// Rock the donut back and forth around the x-axis by up to this many radians while it
+    /// spins...
+    pub wobble_amplitude: f32,
+
+    /// ...going through this many radians of the wobble each frame.
+    pub wobble_frequency: f32,
+
+    /// Color the inside of the donut's tube, facing the hole, and the outside, instead of the
+    /// gradient.
+    pub inner_color: Option<u32>,
+    pub outer_color: Option<u32>,
+
+    /// The 0xRRGGBB colors to shade the surface with, from darkest to brightest. Any number of
+    /// colors works as long as there's at least one.
+    pub palette: Vec<u32>,
+
+    /// Shade with this instead of `palette`, picking colors from it by luminance (0..1) so the
+    /// shading is perfectly smooth.
+    pub cosine_palette: Option<CosinePalette>,
+
+    /// How far to morph the torus into a sphere of the same size, from 0 (a torus) to 1 (a
+    /// sphere).
+    pub morph: f32,
+}
+
+impl RenderConfig {
+    /// The default look at `width * height` pixels, which panics if either is 0. Anything down to
+    /// 1x1 renders, though below a few dozen pixels across there isn't much to see.
+    pub fn new(width: usize, height: usize) -> RenderConfig {
+        assert!(
+            width > 0 && height > 0,
+            "the screen needs to be at least 1x1 pixels, not {}x{}",
+            width,
+            height
+        );
+
+        RenderConfig {
+            width,
+            height,
+            r1: R1,
+            r2: R2,
+            light: LIGHT,
+            cull_threshold: 0.0,
+            terminator_deadzone: 0.0,
+            sparkle: 0.0,
+            sparkle_direction: sparkle_direction(0),
+            checker: None,
+            texture: None,
+            explode_segments: 1,
+            explode_distance: 0.0,
+            grain: 0.0,
+            bloom_threshold: 0.8,
+            bloom_strength: 0.0,
+            outline_glow: None,
+            outline_glow_radius: 6,
+            outline_glow_intensity: 1.0,
+            shading_gamma: 1.0,
+            lum_floor: 0.0,
+            lum_ceil: 1.0,
+            gradient_scroll: 0.0,
+            splat: None,
+            cel: None,
+            reflection: 0.0,
+            debug_axes: false,
+            debug_density: false,
+            debug_overdraw: false,
+            crop: None,
+            cage: None,
+            z_bias: 0.0,
+            z_blend_epsilon: 0.0,
+            z_supersample: 1,
+            crisp_outline: false,
+            shell_thickness: 0.0,
+            fur: None,
+            scale: [1.0; 3],
+            color_depth: ColorDepth::True,
+            vignette: 0.0,
+            scanlines: 0.0,
+            z_near: Z_NEAR,
+            theta_spacing: THETA_SPACING,
+            phi_spacing: PHI_SPACING,
+            point_size: 1,
+            fade_in_frames: 0,
+            reveal_frames: 0,
+            interlace: false,
+            interlace_field: 0,
+            shape: Shape::Torus,
+            screen_offset: [0.0; 2],
+            anaglyph: None,
+            auto_frame: None,
+            silhouette: None,
+            z_mode: ZMode::Depth,
+            melt: 0.0,
+            spin_up_frames: 0,
+            easing: Easing::Linear,
+            wobble_amplitude: 0.0,
+            wobble_frequency: 0.05,
+            inner_color: None,
+            outer_color: None,
+            palette: GRADIENT.to_vec(),
+            cosine_palette: None,
+            morph: 0.0,
+        }
+    }
+}
+
+/// The looks [`preset`] knows, by name, with a description of each.
+pub const PRESETS: [(&str, &str); 4] = [
+    ("classic", "The default gradient, as it's always been"),
+    ("neon", "Glowing pink and cyan"),
+    ("chrome", "Shiny grayscale, sitting on a mirror"),
+    ("retro", "A green CRT, with scanlines and noise"),
+];
+
+/// A config for one of the [`PRESETS`] looks at `width * height` pixels, or None if there's no
+/// preset called `name`. Fields can still be changed afterwards.
+pub fn preset(name: &str, width: usize, height: usize) -> Option<RenderConfig> {
+    let mut config = RenderConfig::new(width, height);
+
+    match name {
+        "classic" => {}
+        "neon" => {
+            config.palette = vec![
+                0x0a0014, 0x22003d, 0x4b0066, 0x80008f, 0xc000b0, 0xff2bd6, 0xff70e8, 0x7ad8ff,
+                0x3ff0ff, 0xb0ffff,
+            ];
+            config.bloom_threshold = 0.5;
+            config.bloom_strength = 0.8;
+            config.shading_gamma = 0.8;
+        }
+        "chrome" => {
+            config.palette = mono_palette(0xffffff, GRADIENT.len());
+            config.shading_gamma = 0.7;
+            config.reflection = 0.6;
+        }
+        "retro" => {
+            config.palette = mono_palette(0x33ff66, GRADIENT.len());
+            config.scanlines = 0.5;
+            config.grain = 0.1;
+            config.vignette = 0.4;
+        }
+        _ => return None,
+    }
+
+    Some(config)
+}
+
+/// How far the donut usually turns around the x-axis each frame, in radians.
+pub const A_STEP: f32 = 0.007;
+
+/// How far the donut usually turns around the z-axis each frame, in radians.
+pub const B_STEP: f32 = 0.003;
+
+/// Fold a steadily increasing (or decreasing) angle back and forth between `min` and `max`, so
+/// instead of spinning all the way round it rocks from one to the other, turning back at each.
+pub fn bounce(angle: f32, (min, max): (f32, f32)) -> f32 {
+    let span = (max - min).max(f32::EPSILON);
+    let t = (angle - min).rem_euclid(2.0 * span);
+    min + if t > span { 2.0 * span - t } else { t }
+}
+
+/// How far through the animation we are: the rotation to pass to `render_frame` and how many
+/// frames have been drawn so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AnimState {
+    pub a: f32,
+    pub b: f32,
+    pub frame: u64,
+}
+
+impl AnimState {
+    /// Move on to the next frame, spinning `speed` times as far as usual.
+    pub fn step(&mut self, speed: f32) {
+        self.step_by(A_STEP * speed, B_STEP * speed);
+    }
+
+    /// Move on to the next frame, turning `a_step` radians around the x-axis and `b_step` around
+    /// the z-axis.
+    pub fn step_by(&mut self, a_step: f32, b_step: f32) {
+        self.a += a_step;
+        self.b += b_step;
+        self.frame += 1;
+    }
+
+    /// Move on to the next frame, going `fraction` of a full turn around the x-axis (and the z-axis
+    /// turning as usual alongside).
+    pub fn turn(&mut self, fraction: f32) {
+        self.step(fraction * 2.0 * PI / A_STEP);
+    }
+}
+
+/// Render the donut rotated by `a` and `b` into `output`, a `width * height` buffer of 0xRRGGBB
+/// pixels. Pixels the donut doesn't cover are left untouched.
+pub fn render_frame(config: &RenderConfig, a: f32, b: f32, output: &mut [u32]) {
+    let mut zbuffer = vec![0.0; config.width * config.height];
+    render_frame_depth(config, a, b, output, &mut zbuffer);
+}
+
+/// Like [`render_frame`], but also leaves the frame's `width * height` z-buffer in `zbuffer`: the
+/// 1/z of the nearest surface drawn at each pixel, or 0 where nothing was. With `anaglyph` it's
+/// whichever eye's surface is nearer.
+pub fn render_frame_depth(
+    config: &RenderConfig,
+    a: f32,
+    b: f32,
+    output: &mut [u32],
+    zbuffer: &mut [Depth],
+) {
+    zbuffer.fill(0.0);
+    let separation = match config.anaglyph {
+        Some(separation) => separation,
+        None => {
+            let mut luminance = vec![0.0; config.width * config.height];
+            render_view(config, a, b, [0.0; 3], output, &mut luminance, zbuffer);
+            return;
+        }
+    };
+
+    // Moving an eye to the left is the same as moving the donut to the right
+    let mut left = output.to_vec();
+    let mut left_zbuffer = vec![0.0; config.width * config.height];
+    let mut luminance = vec![0.0; config.width * config.height];
+    render_view(
+        config,
+        a,
+        b,
+        [separation / 2.0, 0.0, 0.0],
+        &mut left,
+        &mut luminance,
+        &mut left_zbuffer,
+    );
+
+    let mut luminance = vec![0.0; config.width * config.height];
+    render_view(
+        config,
+        a,
+        b,
+        [-separation / 2.0, 0.0, 0.0],
+        output,
+        &mut luminance,
+        zbuffer,
+    );
+
+    for (depth, left) in zbuffer.iter_mut().zip(left_zbuffer) {
+        *depth = depth.max(left);
+    }
+
+    // The gradient has hardly any red in its blues, so each eye sees its view in grayscale rather
+    // than keeping just its own channels of the colors.
+    for (pixel, left) in output.iter_mut().zip(left) {
+        let gray = |color| (post::brightness(color) * 255.0).round() as u32;
+        *pixel = (gray(left) << 16) | (gray(*pixel) * 0x0101);
+    }
+}
+
+/// Render one frame into a newly allocated, black `width * height` buffer and return it. For
+/// one-off frames: `render_frame` can reuse the same buffer frame after frame instead.
+///
+/// ```
+/// let (width, height) = (80, 60);
+/// let config = donut::RenderConfig::new(width, height);
+/// let buf = donut::frame(&config, 1.0, 0.5);
+/// assert_eq!(buf.len(), width * height);
+/// assert!(buf.iter().any(|&pixel| pixel != 0));
+/// ```
+pub fn frame(config: &RenderConfig, a: f32, b: f32) -> Vec<u32> {
+    let mut output = vec![0; config.width * config.height];
+    render_frame(config, a, b, &mut output);
+    output
+}
+
+/// Render one frame and show it in a window the caller already has open, for hosting the donut in
+/// a bigger minifb app. The window is updated with the `width * height` frame, fitted to it
+/// however the window's scale mode says.
+pub fn draw_into_window(
+    window: &mut minifb::Window,
+    config: &RenderConfig,
+    a: f32,
+    b: f32,
+) -> minifb::Result<()> {
+    window.update_with_buffer(&frame(config, a, b), config.width, config.height)
+}
+
+/// Whether the donut covers the pixel at (`x`, `y`) when rotated by `a` and `b`. Parts of the
+/// surface facing away from the light aren't drawn, so they don't count.
+pub fn is_donut_pixel(config: &RenderConfig, x: usize, y: usize, a: f32, b: f32) -> bool {
+    if x >= config.width || y >= config.height {
+        return false;
+    }
+
+    coverage(config, a, b, [0.0; 3])[xy(config.width, x, y)]
+}
+
+// Which pixels the shape covers, moved by `offset` in world space
+fn coverage(config: &RenderConfig, a: f32, b: f32, offset: [f32; 3]) -> Vec<bool> {
+    // Anything plotted leaves a 1/z above 0 in the z-buffer, whatever color it was
+    zbuffer(config, a, b, offset)
+        .iter()
+        .map(|&ooz| ooz > 0.0)
+        .collect()
+}
+
+// The z-buffer left after rendering the shape moved by `offset` in world space
+fn zbuffer(config: &RenderConfig, a: f32, b: f32, offset: [f32; 3]) -> Vec<Depth> {
+    let mut zbuffer = vec![0.0; config.width * config.height];
+    let mut luminance = vec![0.0; config.width * config.height];
+    let mut output = vec![0; config.width * config.height];
+    let mut target = Target::new(&mut zbuffer, &mut luminance, &mut output);
+    render_shape(config, a, b, offset, &mut target);
+
+    zbuffer
+}
+
+/// What ended up in the z-buffer for a frame, from [`depth_stats`].
+#[derive(Debug)]
+pub struct DepthStats {
+    /// How many pixels the donut covers.
+    pub covered: usize,
+
+    /// The smallest, largest, and mean 1/z over the covered pixels (larger is nearer), or 0 if
+    /// there aren't any.
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+}
+
+impl std::fmt::Display for DepthStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} pixels covered, 1/z from {:.4} to {:.4} (mean {:.4})",
+            self.covered, self.min, self.max, self.mean
+        )
+    }
+}
+
+/// Look at the depths in a frame's z-buffer, from [`render_frame_depth`] (or the row and ring
+/// versions), rather than its colors. Useful for seeing how close together surfaces get.
+pub fn depth_stats(zbuffer: &[Depth]) -> DepthStats {
+    let covered: Vec<f32> = zbuffer
+        .iter()
+        .filter(|&&ooz| ooz > 0.0)
+        .map(|&ooz| as_f32(ooz))
+        .collect();
+
+    if covered.is_empty() {
+        return DepthStats {
+            covered: 0,
+            min: 0.0,
+            max: 0.0,
+            mean: 0.0,
+        };
+    }
+
+    DepthStats {
+        covered: covered.len(),
+        min: covered.iter().copied().fold(f32::INFINITY, f32::min),
+        max: covered.iter().copied().fold(0.0, f32::max),
+        mean: covered.iter().sum::<f32>() / covered.len() as f32,
+    }
+}
+
+/// Like [`render_frame`], then hands the finished frame to `on_frame` so embedders can draw their
+/// own content (a HUD, say) over the donut.
+pub fn render_frame_with(
+    config: &RenderConfig,
+    a: f32,
+    b: f32,
+    output: &mut [u32],
+    on_frame: impl FnOnce(&mut [u32]),
+) {
+    render_frame(config, a, b, output);
+    on_frame(output);
+}
+
+/// Like [`render_frame`], but also records the linear shading behind each plotted pixel's color in
+/// `luminance`, before the gradient quantizes it: from 0 where the surface is edge-on to the light
+/// up to 1 where it faces it head on (after `shading_gamma`). Uncovered pixels are left untouched.
+pub fn render_frame_luminance(
+    config: &RenderConfig,
+    a: f32,
+    b: f32,
+    output: &mut [u32],
+    luminance: &mut [f32],
+) {
+    let mut zbuffer = vec![0.0; config.width * config.height];
+    render_view(config, a, b, [0.0; 3], output, luminance, &mut zbuffer);
+}
+
+// Render the shape, moved by `offset` in world space, into `zbuffer`, which starts out empty
+fn render_view(
+    config: &RenderConfig,
+    a: f32,
+    b: f32,
+    offset: [f32; 3],
+    output: &mut [u32],
+    luminance: &mut [f32],
+    zbuffer: &mut [Depth],
+) {
+    if let Some(color) = config.silhouette {
+        let (width, height) = (config.width, config.height);
+        let depth = self::zbuffer(config, a, b, offset);
+        let covered: Vec<bool> = depth.iter().map(|&ooz| ooz > 0.0).collect();
+
+        // Edge pixels are covered ones next to uncovered ones. Past the edge of the screen
+        // doesn't count as uncovered, so a donut running off screen isn't outlined along the edge.
+        let uncovered = |x: usize, y: usize| x < width && y < height && !covered[xy(width, x, y)];
+        for y in 0..height {
+            for x in 0..width {
+                let edge = uncovered(x.wrapping_sub(1), y)
+                    || uncovered(x + 1, y)
+                    || uncovered(x, y.wrapping_sub(1))
+                    || uncovered(x, y + 1);
+
+                if covered[xy(width, x, y)] && edge {
+                    output[xy(width, x, y)] = color;
+                    zbuffer[xy(width, x, y)] = depth[xy(width, x, y)];
+                }
+            }
+        }
+
+        return;
+    }
+
+    if config.debug_density {
+        plot_density(config, a, b, offset, output, zbuffer);
+        return;
+    }
+
+    if config.debug_overdraw {
+        plot_overdraw(config, a, b, offset, output, zbuffer);
+        return;
+    }
+
+    // The reflection goes down first, with its own z-buffer, so the donut is drawn over it rather
+    // than having to be in front of it.
+    if config.reflection > 0.0 {
+        let mut zbuffer = vec![0.0; config.width * config.height];
+        let mut luminance = vec![0.0; config.width * config.height];
+        let mut target = Target {
+            mirror: Some(-lowest_point(config, a, b)),
+            ..Target::new(&mut zbuffer, &mut luminance, output)
+        };
+        render_shape(config, a, b, offset, &mut target);
+    }
+
+    let background = config.splat.map(|_| output.to_vec());
+
+    let mut fine = (config.z_supersample > 1).then(|| Supersample::new(config));
+    let mut target = Target {
+        fine: fine.as_mut(),
+        ..Target::new(zbuffer, luminance, output)
+    };
+    render_shape(config, a, b, offset, &mut target);
+    if let Some(fine) = target.fine.take() {
+        fine.resolve(config, &mut target);
+    }
+
+    if let Some(background) = background {
+        soften(config, a, b, offset, &background, &mut target);
+    }
+
+    if config.shell_thickness > 0.0 {
+        shade_shell(config, a, b, offset, &mut target);
+    }
+
+    if let Some(bands) = config.cel {
+        outline_bands(config, bands, &mut target);
+    }
+
+    if config.crisp_outline {
+        crisp_outline(config, a, b, offset, &mut target);
+    }
+
+    if let (Some(color), Shape::Torus) = (config.cage, config.shape) {
+        plot_cage(config, a, b, offset, color, &mut target);
+    }
+
+    if config.debug_axes {
+        plot_axes(config, a, b, offset, &mut target);
+    }
+}
+
+// `RenderConfig::debug_density` colors pixels along these, from the fewest samples to the most
+const DENSITY_COLORS: [u32; 5] = [0x0000ff, 0x00ffff, 0x00ff00, 0xffff00, 0xff0000];
+
+// Count the lit samples landing on each pixel and color the pixels by how many, relative to the
+// busiest pixel. Pixels no samples land on are left alone.
+fn plot_density(
+    config: &RenderConfig,
+    a: f32,
+    b: f32,
+    offset: [f32; 3],
+    output: &mut [u32],
+    zbuffer: &mut [Depth],
+) {
+    let mut luminance = vec![0.0; config.width * config.height];
+    let mut density = vec![0; config.width * config.height];
+    let mut target = Target {
+        density: Some(&mut density),
+        ..Target::new(zbuffer, &mut luminance, output)
+    };
+    render_shape(config, a, b, offset, &mut target);
+    heatmap(&density, output);
+}
+
+// Render the shape, counting how many times each pixel is drawn over after the first sample lands
+// on it, then color the pixels drawn over at least once by how many times.
+fn plot_overdraw(
+    config: &RenderConfig,
+    a: f32,
+    b: f32,
+    offset: [f32; 3],
+    output: &mut [u32],
+    zbuffer: &mut [Depth],
+) {
+    let mut luminance = vec![0.0; config.width * config.height];
+    let mut overdraw = vec![0; config.width * config.height];
+    let mut target = Target {
+        overdraw: Some(&mut overdraw),
+        ..Target::new(zbuffer, &mut luminance, output)
+    };
+    render_shape(config, a, b, offset, &mut target);
+    heatmap(&overdraw, output);
+}
+
+// Color each pixel with a nonzero count along `DENSITY_COLORS`, relative to the largest count
+fn heatmap(counts: &[u32], output: &mut [u32]) {
+    let most = counts.iter().copied().max().unwrap_or(0);
+    for (pixel, &count) in output.iter_mut().zip(counts) {
+        if count > 0 {
+            // From a single sample at the first color up to the busiest pixel at the last
+            let t = (count - 1) as f32 / (most - 1).max(1) as f32;
+            let position = t * (DENSITY_COLORS.len() - 1) as f32;
+            let i = (position as usize).min(DENSITY_COLORS.len() - 2);
+            *pixel = lerp_color(
+                DENSITY_COLORS[i],
+                DENSITY_COLORS[i + 1],
+                position - i as f32,
+            );
+        }
+    }
+}
+
+// With `RenderConfig::splat`, samples only count towards a pixel if their 1/z is within this
+// fraction of the nearest surface there, so the far side of the donut doesn't show through.
+const SPLAT_DEPTH_TOLERANCE: f32 = 0.02;
+
+// Redraw what's in `target` as soft blobs over `background`. The shape is rendered again with each
+// sample spread over the pixels around it, then each pixel gets the weighted average of the colors
+// which landed on it, blended over the background by how much landed compared to a typical
+// covered pixel.
+fn soften(
+    config: &RenderConfig,
+    a: f32,
+    b: f32,
+    offset: [f32; 3],
+    background: &[u32],
+    target: &mut Target,
+) {
+    let mut accumulation = vec![[0.0; 4]; config.width * config.height];
+    let mut luminance = vec![0.0; config.width * config.height];
+    let mut output = vec![0; config.width * config.height];
+    render_shape(
+        config,
+        a,
+        b,
+        offset,
+        &mut Target {
+            splat: Some(&mut accumulation),
+            ..Target::new(target.zbuffer, &mut luminance, &mut output)
+        },
+    );
+
+    let covered: Vec<f32> = (0..accumulation.len())
+        .filter(|&i| target.zbuffer[i] > 0.0)
+        .map(|i| accumulation[i][3])
+        .collect();
+    let typical = covered.iter().sum::<f32>() / covered.len().max(1) as f32;
+
+    for (i, &[r, g, b, weight]) in accumulation.iter().enumerate() {
+        let in_field = !config.interlace || i / config.width % 2 == config.interlace_field;
+        if !in_field {
+            continue;
+        }
+
+        target.output[i] = if weight > 0.0 {
+            let channel = |c: f32| (c / weight).round().min(255.0) as u32;
+            let color = channel(r) << 16 | channel(g) << 8 | channel(b);
+            lerp_color(background[i], color, (weight / typical).min(1.0))
+        } else {
+            background[i]
+        };
+    }
+}
+
+// Add a sample at (`xp`, `yp`) on screen to the pixels around it, weighted by a Gaussian falling
+// off over `radius` pixels, wherever it's on the nearest surface in `front`.
+#[allow(clippy::too_many_arguments)]
+fn splat(
+    config: &RenderConfig,
+    front: &[Depth],
+    accumulation: &mut [[f32; 4]],
+    radius: f32,
+    (xp, yp): (f32, f32),
+    depth: Depth,
+    color: u32,
+) {
+    let (width, height) = (config.width, config.height);
+    let reach = radius.ceil() as isize;
+    let sigma = radius / 2.0;
+    let channels = [(color >> 16) & 0xff, (color >> 8) & 0xff, color & 0xff];
+
+    for dy in -reach..=reach {
+        for dx in -reach..=reach {
+            let x = xp.floor() as isize + dx;
+            let y = yp.floor() as isize + dy;
+            let on_screen = x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height;
+            let in_field = !config.interlace || y as usize % 2 == config.interlace_field;
+            if !on_screen || !in_field {
+                continue;
+            }
+
+            let index = xy(width, x as usize, y as usize);
+            let hidden = depth < front[index] * as_depth(1.0 - SPLAT_DEPTH_TOLERANCE);
+            let distance2 = (x as f32 + 0.5 - xp).powi(2) + (y as f32 + 0.5 - yp).powi(2);
+            if hidden || distance2 > radius * radius {
+                continue;
+            }
+
+            let weight = (-distance2 / (2.0 * sigma * sigma)).exp();
+            let sum = &mut accumulation[index];
+            for (sum, &channel) in sum.iter_mut().zip(&channels) {
+                *sum += channel as f32 * weight;
+            }
+            sum[3] += weight;
+        }
+    }
+}
+
+/// The color of the lines between `RenderConfig::cel` bands.
+pub const CEL_OUTLINE: u32 = 0x000000;
+
+// Which of `bands` equal slices of 0..1 the luminance `l` falls in
+fn cel_band(l: f32, bands: usize) -> usize {
+    ((l * bands as f32) as usize).min(bands.max(1) - 1)
+}
+
+// Outline each band on its brighter side, so lines are a single pixel wide. The background counts
+// as darker than any band, so the edge of the donut is outlined too, but past the edge of the screen
+// doesn't count and neither do rows left out of this interlaced field.
+fn outline_bands(config: &RenderConfig, bands: usize, target: &mut Target) {
+    let (width, height) = (config.width, config.height);
+    let row_step = if config.interlace { 2 } else { 1 };
+    let band = |x: usize, y: usize| {
+        let index = xy(width, x, y);
+        if target.zbuffer[index] > 0.0 {
+            Some(cel_band(target.luminance[index], bands))
+        } else {
+            None
+        }
+    };
+
+    let mut edges = Vec::new();
+    for y in 0..height {
+        if config.interlace && y % 2 != config.interlace_field {
+            continue;
+        }
+
+        for x in 0..width {
+            let here = match band(x, y) {
+                Some(here) => here,
+                None => continue,
+            };
+
+            let darker = |x: usize, y: usize| x < width && y < height && band(x, y) < Some(here);
+            if darker(x.wrapping_sub(1), y)
+                || darker(x + 1, y)
+                || darker(x, y.wrapping_sub(row_step))
+                || darker(x, y + row_step)
+            {
+                edges.push(xy(width, x, y));
+            }
+        }
+    }
+
+    for index in edges {
+        target.output[index] = CEL_OUTLINE;
+    }
+}
+
+// How dark `RenderConfig::shell_thickness` makes the rim, where nothing of the inside of the shell
+// is behind the outside, from 0 (not at all) to 1 (black)
+const SHELL_RIM_DARKNESS: f32 = 0.8;
+
+// Render the inside of the shell into its own z-buffer, then darken each pixel of the outside by
+// how much further than the shell's thickness it is to the inside behind it. Looking straight
+// through the wall it's about the thickness, growing towards the rim as the wall is seen more
+// edge-on, until past the inside's silhouette there's nothing behind at all.
+fn shade_shell(config: &RenderConfig, a: f32, b: f32, offset: [f32; 3], target: &mut Target) {
+    // All of the inside counts, lit or not, since it's only the depth that's wanted
+    let mut inside = config.clone();
+    inside.cull_threshold = -1.0;
+    inside.terminator_deadzone = 0.0;
+
+    let pixels = config.width * config.height;
+    let mut zbuffer = vec![0.0; pixels];
+    let mut luminance = vec![0.0; pixels];
+    let mut output = vec![0; pixels];
+    render_shape(
+        &inside,
+        a,
+        b,
+        offset,
+        &mut Target {
+            inset: config.shell_thickness.min(config.r1),
+            ..Target::new(&mut zbuffer, &mut luminance, &mut output)
+        },
+    );
+
+    for y in 0..config.height {
+        if config.interlace && y % 2 != config.interlace_field {
+            continue;
+        }
+
+        for x in 0..config.width {
+            let index = xy(config.width, x, y);
+            let (outer, inner) = (target.zbuffer[index], zbuffer[index]);
+            if outer <= 0.0 {
+                continue;
+            }
+
+            let rim = if inner > 0.0 {
+                let gap = as_f32(1.0 / inner - 1.0 / outer);
+                1.0 - (config.shell_thickness / gap.max(f32::EPSILON)).min(1.0)
+            } else {
+                1.0
+            };
+            let output = &mut target.output[index];
+            *output = lerp_color(*output, 0, rim * SHELL_RIM_DARKNESS);
+        }
+    }
+}
+
+// `RenderConfig::crisp_outline` counts a change in 1/z across a pixel of the finer z-buffer as an
+// edge if it's at least this fraction of the nearest 1/z around it
+const CRISP_EDGE_THRESHOLD: f32 = 0.3;
+
+// Render the shape's z-buffer again at twice the resolution, find the edges in it with a Sobel
+// filter, and darken each pixel by how many of the finer pixels it covers are on an edge.
+fn crisp_outline(config: &RenderConfig, a: f32, b: f32, offset: [f32; 3], target: &mut Target) {
+    // Everything measured in pixels doubles, and samples need to be twice as close together
+    let mut fine = config.clone();
+    fine.width *= 2;
+    fine.height *= 2;
+    fine.point_size *= 2;
+    fine.theta_spacing /= 2.0;
+    fine.phi_spacing /= 2.0;
+    fine.screen_offset = config.screen_offset.map(|offset| offset * 2.0);
+    fine.crop = config.crop.map(|crop| crop.map(|edge| edge * 2.0));
+    fine.auto_frame = config.auto_frame.map(|margin| margin * 2.0);
+    fine.interlace = false;
+
+    let (width, height) = (fine.width, fine.height);
+    let depth: Vec<f32> = zbuffer(&fine, a, b, offset)
+        .iter()
+        .map(|&depth| as_f32(depth))
+        .collect();
+
+    let mut edges = vec![false; width * height];
+    for y in 1..height.saturating_sub(1) {
+        for x in 1..width - 1 {
+            let d = |dx: isize, dy: isize| {
+                depth[xy(
+                    width,
+                    (x as isize + dx) as usize,
+                    (y as isize + dy) as usize,
+                )]
+            };
+            let gx = d(1, -1) + 2.0 * d(1, 0) + d(1, 1) - d(-1, -1) - 2.0 * d(-1, 0) - d(-1, 1);
+            let gy = d(-1, 1) + 2.0 * d(0, 1) + d(1, 1) - d(-1, -1) - 2.0 * d(0, -1) - d(1, -1);
+
+            // The Sobel kernels have a total weight of 4 on each side
+            let nearest = (-1..=1)
+                .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                .map(|(dx, dy)| d(dx, dy))
+                .fold(0.0, f32::max);
+            let change = (gx * gx + gy * gy).sqrt() / 4.0;
+            edges[xy(width, x, y)] = nearest > 0.0 && change >= CRISP_EDGE_THRESHOLD * nearest;
+        }
+    }
+
+    for y in 0..config.height {
+        if config.interlace && y % 2 != config.interlace_field {
+            continue;
+        }
+
+        for x in 0..config.width {
+            let on_edge = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                .iter()
+                .filter(|&&(dx, dy)| edges[xy(width, 2 * x + dx, 2 * y + dy)])
+                .count();
+
+            if on_edge > 0 {
+                let index = xy(config.width, x, y);
+                let t = on_edge as f32 / 4.0;
+                target.output[index] = lerp_color(target.output[index], CEL_OUTLINE, t);
+            }
+        }
+    }
+}
+
+/// The screen-space box `(min_x, min_y, max_x, max_y)` which `render_frame` can draw into over any
+/// rotation of the donut.
+///
+/// Every point on the donut is within R1 + R2 of its center, and the outer edge of the tube
+/// reaches that distance in any direction for some orientation, so the donut sweeps out exactly a
+/// sphere. The box is where the lines from the viewer which just graze that sphere hit the screen.
+/// If the viewer is inside the sphere the donut can cover anything, and the box is infinite.
+pub fn projected_bounds(config: &RenderConfig) -> (f32, f32, f32, f32) {
+    let slope = match bounding_slope(config) {
+        Some(slope) => slope,
+        None => {
+            return (
+                f32::NEG_INFINITY,
+                f32::NEG_INFINITY,
+                f32::INFINITY,
+                f32::INFINITY,
+            )
+        }
+    };
+    let extent = k1(config) * slope;
+
+    let (center_x, center_y) = (config.width as f32 / 2.0, config.height as f32 / 2.0);
+    (
+        center_x - extent,
+        center_y - extent,
+        center_x + extent,
+        center_y + extent,
+    )
+}
+
+// The slope x/z of the lines from the viewer which just graze the donut's bounding sphere, or
+// None if the viewer is inside it. The grazing line touches the sphere where it's perpendicular to
+// the radius, so its slope is the tangent of the angle the sphere subtends.
+fn bounding_slope(config: &RenderConfig) -> Option<f32> {
+    // Exploding pushes the bands of the tube out from the ring, fur reaches out past the surface,
+    // stretching can push points out as far as the largest scale times their distance, and melting
+    // drags the lowest ones down by the most the drips reach at that distance
+    let stretch = config.scale.iter().fold(0.0f32, |max, s| max.max(s.abs()));
+    let reach = config.r1 + config.r2 + config.explode_distance + config.fur.unwrap_or(0.0);
+    let radius = reach * stretch;
+    let radius = radius + config.melt * MELT_DEPTH * (1.0 + MELT_DRIP) * radius * radius;
+    if K2 <= radius {
+        return None;
+    }
+
+    Some(radius / (K2 * K2 - radius * radius).sqrt())
+}
+
+/// Set the torus's `theta_spacing` and `phi_spacing` as far apart as they can go while
+/// [`check_sampling_density`] still passes, for the fastest render without gaps. Both are brought
+/// closer together first if they already leave gaps. Only the torus is sampled this way, so other
+/// shapes are left alone.
+pub fn auto_tune_spacing(config: &mut RenderConfig) {
+    if config.shape != Shape::Torus {
+        return;
+    }
+
+    let passes = |config: &RenderConfig| check_sampling_density(config).is_none();
+    let spacings: [fn(&mut RenderConfig) -> &mut f32; 2] = [
+        |config| &mut config.theta_spacing,
+        |config| &mut config.phi_spacing,
+    ];
+
+    while !passes(config) && config.theta_spacing.max(config.phi_spacing) > f32::EPSILON {
+        config.theta_spacing /= TUNE_STEP;
+        config.phi_spacing /= TUNE_STEP;
+    }
+
+    for spacing in spacings {
+        loop {
+            let before = *spacing(config);
+            *spacing(config) = (before * TUNE_STEP).min(TUNE_LIMIT);
+            if !passes(config) || before == TUNE_LIMIT {
+                *spacing(config) = before;
+                break;
+            }
+        }
+    }
+}
+
+/// Returned by [`check_sampling_density`] when samples can land too far apart to cover every
+/// pixel between them.
+#[derive(Debug)]
+pub struct SamplingWarning {
+    /// The furthest apart neighbouring samples can be on screen, in pixels.
+    pub spacing: f32,
+}
+
+impl std::fmt::Display for SamplingWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "samples can be up to {:.1} pixels apart at this resolution, so expect gaps in the \
+             surface",
+            self.spacing
+        )
+    }
+}
+
+/// Check whether the surface is sampled densely enough to leave no gaps at the config's
+/// resolution.
+pub fn check_sampling_density(config: &RenderConfig) -> Option<SamplingWarning> {
+    // The furthest apart neighbouring samples get in world space. On the torus theta steps around
+    // the tube and phi steps around the outer edge, which is furthest from the axis.
+    let step = match config.shape {
+        Shape::Torus => {
+            let r1 = config.r1 + config.r2 * config.morph;
+            let r2 = config.r2 * (1.0 - config.morph);
+            let outer = r2 + r1 + config.explode_distance;
+
+            (config.theta_spacing * r1).max(config.phi_spacing * outer)
+        }
+        Shape::Cylinder { .. } => CYLINDER_SPACING,
+    };
+
+    // Stretching across the screen spreads the samples out by as much, and stretching in depth
+    // brings the nearest of them closer. Samples are furthest apart on screen where the surface
+    // comes closest to the viewer. Cropping zooms in, spreading them out as much again.
+    let [sx, sy, sz] = config.scale.map(f32::abs);
+    let nearest = (K2 - (config.r1 + config.r2) * sz).max(config.z_near);
+    let zoom = config.crop.map_or(1.0, |[_, _, width, height]| {
+        (config.width as f32 / width).max(config.height as f32 / height)
+    });
+    let spacing = k1(config) * step * sx.max(sy) * zoom / nearest;
+
+    // Each sample covers a point_size wide disc
+    if spacing > config.point_size.max(1) as f32 {
+        Some(SamplingWarning { spacing })
+    } else {
+        None
+    }
+}
+
+// How much nearer the `i`th of several donuts is treated as being, see `RenderConfig::z_bias`
+fn z_bias(config: &RenderConfig, i: usize) -> Depth {
+    as_depth(i as f32 * config.z_bias)
+}
+
+// The rotation phase added to each successive donut in a row
+const ROW_PHASE: f32 = 0.4;
+
+/// Render `n` donuts side by side along the x-axis, each spinning slightly out of phase with its
+/// neighbour. The donuts are spaced so their rims overlap and pushed back far enough that the whole
+/// row fits on screen.
+pub fn render_row(config: &RenderConfig, n: usize, a: f32, b: f32, output: &mut [u32]) {
+    let mut zbuffer = vec![0.0; config.width * config.height];
+    render_row_depth(config, n, a, b, output, &mut zbuffer);
+}
+
+/// Like [`render_row`], but also leaves the row's z-buffer in `zbuffer`, as
+/// [`render_frame_depth`] does.
+pub fn render_row_depth(
+    config: &RenderConfig,
+    n: usize,
+    a: f32,
+    b: f32,
+    output: &mut [u32],
+    zbuffer: &mut [Depth],
+) {
+    // Each donut reaches R1 + R2 out from its center, so centers 2 * R2 apart leave the tubes of
+    // neighbouring donuts overlapping.
+    let (r1, r2) = (config.r1, config.r2);
+    let spacing = 2.0 * r2;
+    let half_extent = (n.max(1) - 1) as f32 * spacing / 2.0 + (r1 + r2);
+
+    // K1 is chosen so a single donut's half-width of R1 + R2 fills 3/8ths of the screen at K2, so
+    // scaling the distance by the row's half-extent keeps the same margin.
+    let depth = K2 * half_extent / (r1 + r2) - K2;
+
+    zbuffer.fill(0.0);
+    let mut luminance = vec![0.0; config.width * config.height];
+    let mut target = Target::new(zbuffer, &mut luminance, output);
+
+    for i in 0..n {
+        let x = i as f32 * spacing - half_extent + (r1 + r2);
+        let phase = i as f32 * ROW_PHASE;
+        target.set_bias(z_bias(config, i));
+
+        render_shape(config, a + phase, b + phase, [x, 0.0, depth], &mut target);
+    }
+}
+
+// How far `render_ring`'s circle leans back from facing the viewer, in radians, so the donuts at
+// the front pass in front of the ones at the back
+const RING_TILT: f32 = 1.2;
+
+/// Render `n` donuts evenly spaced around a circle of radius `radius` (in world units, where a
+/// donut is usually 6 across), like a ferris wheel tipped back so its front passes in front of its
+/// back. Turning around the z-axis by `b` turns the wheel as well as each donut, which faces along
+/// the circle. The whole wheel is pushed back far enough to fit on screen.
+pub fn render_ring(
+    config: &RenderConfig,
+    n: usize,
+    radius: f32,
+    a: f32,
+    b: f32,
+    output: &mut [u32],
+) {
+    let mut zbuffer = vec![0.0; config.width * config.height];
+    render_ring_depth(config, n, radius, a, b, output, &mut zbuffer);
+}
+
+/// Like [`render_ring`], but also leaves the ring's z-buffer in `zbuffer`, as
+/// [`render_frame_depth`] does.
+pub fn render_ring_depth(
+    config: &RenderConfig,
+    n: usize,
+    radius: f32,
+    a: f32,
+    b: f32,
+    output: &mut [u32],
+    zbuffer: &mut [Depth],
+) {
+    // Pushed back in the same way as a row, so the wheel's half-width fits where a donut would
+    let (r1, r2) = (config.r1, config.r2);
+    let depth = K2 * (radius + r1 + r2) / (r1 + r2) - K2;
+    let (sin_tilt, cos_tilt) = sin_cos(RING_TILT);
+
+    zbuffer.fill(0.0);
+    let mut luminance = vec![0.0; config.width * config.height];
+    let mut target = Target::new(zbuffer, &mut luminance, output);
+
+    for i in 0..n {
+        let angle = i as f32 * 2.0 * PI / n as f32 + b;
+        let (sin, cos) = sin_cos(angle);
+        let (x, y) = (radius * cos, radius * sin);
+        let offset = [x, y * cos_tilt, depth + y * sin_tilt];
+        target.set_bias(z_bias(config, i));
+
+        render_shape(config, a, angle, offset, &mut target);
+    }
+}
+
+// Pixel offsets covering a disc `size` pixels across, just the center pixel for size 1
+fn disc(size: usize) -> Vec<(isize, isize)> {
+    let radius = size.max(1) as f32 / 2.0;
+    let reach = radius as isize;
+
+    let mut offsets = Vec::new();
+    for dy in -reach..=reach {
+        for dx in -reach..=reach {
+            if ((dx * dx + dy * dy) as f32) <= radius * radius {
+                offsets.push((dx, dy));
+            }
+        }
+    }
+
+    offsets
+}
+
+// The precision of the z-buffer. With the `f64-depth` feature it takes twice the memory (8 bytes a
+// pixel rather than 4) but can tell apart surfaces which are too close together in depth for f32.
+#[cfg(not(feature = "f64-depth"))]
+pub type Depth = f32;
+#[cfg(feature = "f64-depth")]
+pub type Depth = f64;
+
+// Convert to and from the z-buffer's precision, which is only a real conversion with `f64-depth`
+#[inline]
+fn as_depth(x: f32) -> Depth {
+    Depth::from(x)
+}
+
+#[allow(clippy::unnecessary_cast)]
+#[inline]
+fn as_f32(depth: Depth) -> f32 {
+    depth as f32
+}
+
+// Reflections fade out completely this many times R1 + R2 from the mirror, which is as far as the
+// top of the donut can be.
+const REFLECTION_FADE: f32 = 2.0;
+
+// The buffers a frame is drawn into, all `width * height` and indexed alike
+struct Target<'a> {
+    zbuffer: &'a mut [Depth],
+    luminance: &'a mut [f32],
+    output: &'a mut [u32],
+
+    // The height of the plane to reflect everything in before plotting it, relative to the shape's
+    // center, when drawing the reflection for `RenderConfig::reflection`
+    mirror: Option<f32>,
+
+    // Where `RenderConfig::splat` adds up its samples instead of plotting them: the red, green,
+    // and blue weighted by how near each sample was, and the total weight
+    splat: Option<&'a mut [[f32; 4]]>,
+
+    // Where `RenderConfig::debug_density` counts how many samples land on each pixel instead of
+    // plotting them
+    density: Option<&'a mut [u32]>,
+
+    // Where `RenderConfig::debug_overdraw` counts how many times each pixel is plotted over
+    overdraw: Option<&'a mut [u32]>,
+
+    // How much nearer than they really are to treat samples in the z-buffer test, so one of
+    // several donuts can win where they meet, see `RenderConfig::z_bias`. The z-buffer keeps the
+    // real depths, and `biases` the bias each pixel's sample was tested with, empty until there's
+    // been one.
+    bias: Depth,
+    biases: Vec<Depth>,
+
+    // How much thinner than `RenderConfig::r1` to make the tube, for the inside of the shell in
+    // `RenderConfig::shell_thickness`
+    inset: f32,
+
+    // Where samples are z-tested and plotted at a finer resolution instead, for
+    // `RenderConfig::z_supersample`, until `Supersample::resolve` brings them back down
+    fine: Option<&'a mut Supersample>,
+}
+
+// A z-buffer (and the colors and luminance to go with it) `factor` times as fine as the output in
+// each direction
+struct Supersample {
+    factor: usize,
+    width: usize,
+    zbuffer: Vec<Depth>,
+    biases: Vec<Depth>,
+    luminance: Vec<f32>,
+    output: Vec<u32>,
+}
+
+impl Supersample {
+    fn new(config: &RenderConfig) -> Supersample {
+        let factor = config.z_supersample;
+        let size = config.width * config.height * factor * factor;
+        Supersample {
+            factor,
+            width: config.width * factor,
+            zbuffer: vec![0.0; size],
+            biases: vec![0.0; size],
+            luminance: vec![0.0; size],
+            output: vec![0; size],
+        }
+    }
+
+    // Z-test and plot a sample at its projected position, which lands in one of the finer pixels,
+    // treating it as `bias` nearer than it is like `Target::plot` does
+    fn plot(
+        &mut self,
+        config: &RenderConfig,
+        (xp, yp): (f32, f32),
+        (ooz, bias): (Depth, Depth),
+        l: f32,
+        color: u32,
+    ) {
+        let height = self.zbuffer.len() / self.width;
+        let x = (xp * self.factor as f32).floor() as isize;
+        let y = (yp * self.factor as f32).floor() as isize;
+
+        let on_screen = x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < height;
+        let in_field = !config.interlace || y as usize / self.factor % 2 == config.interlace_field;
+        if !on_screen || !in_field {
+            return;
+        }
+
+        let index = xy(self.width, x as usize, y as usize);
+        let nearer = ooz + bias - (self.zbuffer[index] + self.biases[index]);
+        if config.z_mode == ZMode::Painter || nearer > 0.0 {
+            self.zbuffer[index] = ooz;
+            self.biases[index] = bias;
+            self.luminance[index] = l;
+            self.output[index] = color;
+        }
+    }
+
+    // Give each of the target's pixels the color of the nearest sample among the finer pixels it
+    // covers, leaving it alone if there aren't any
+    fn resolve(&self, config: &RenderConfig, target: &mut Target) {
+        for y in 0..config.height {
+            for x in 0..config.width {
+                let nearest = (0..self.factor * self.factor)
+                    .map(|i| {
+                        let (dx, dy) = (i % self.factor, i / self.factor);
+                        xy(self.width, x * self.factor + dx, y * self.factor + dy)
+                    })
+                    .filter(|&i| self.zbuffer[i] > 0.0)
+                    .max_by(|&i, &j| {
+                        let biased = |i: usize| self.zbuffer[i] + self.biases[i];
+                        biased(i).total_cmp(&biased(j))
+                    });
+
+                if let Some(i) = nearest {
+                    let index = xy(config.width, x, y);
+                    target.zbuffer[index] = self.zbuffer[i];
+                    target.luminance[index] = self.luminance[i];
+                    target.output[index] = self.output[i];
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Target<'a> {
+    // Draw into these buffers, with none of the extras for particular passes
+    fn new(
+        zbuffer: &'a mut [Depth],
+        luminance: &'a mut [f32],
+        output: &'a mut [u32],
+    ) -> Target<'a> {
+        Target {
+            zbuffer,
+            luminance,
+            output,
+            mirror: None,
+            splat: None,
+            density: None,
+            overdraw: None,
+            bias: 0.0,
+            biases: Vec::new(),
+            inset: 0.0,
+            fine: None,
+        }
+    }
+
+    // Where a point relative to the shape's center shows up, and how brightly: mirrored in the
+    // plane and fading the further it is from it, or as it is if there's no mirror. Points below
+    // the plane won't show up in it at all.
+    fn reflect(&self, config: &RenderConfig, [x, y, z]: [f32; 3]) -> ([f32; 3], f32) {
+        match self.mirror {
+            Some(plane) => {
+                let above = y - plane;
+                let fade = 1.0 - above / (REFLECTION_FADE * (config.r1 + config.r2));
+                let brightness = if above < 0.0 { 0.0 } else { fade };
+                ([x, plane - above, z], config.reflection * brightness)
+            }
+            None => ([x, y, z], 1.0),
+        }
+    }
+
+    // Treat the samples plotted from now on as `bias` nearer than they are
+    fn set_bias(&mut self, bias: Depth) {
+        if bias != 0.0 && self.biases.is_empty() {
+            self.biases = vec![0.0; self.zbuffer.len()];
+        }
+        self.bias = bias;
+    }
+
+    // Darken a shaded sample by the brightness `reflect` gave, if it's a reflection
+    fn dim(&self, brightness: f32, l: f32, color: u32) -> (f32, u32) {
+        if self.mirror.is_some() {
+            (l * brightness, lerp_color(0, color, brightness))
+        } else {
+            (l, color)
+        }
+    }
+
+    // Plot a sample at `index` if it passes the z-buffer test. Larger 1/z means the pixel is closer
+    // to the viewer than what's already plotted.
+    //
+    // In ZMode::Painter every sample is plotted, in the order it comes.
+    fn plot(&mut self, config: &RenderConfig, index: usize, ooz: Depth, l: f32, color: u32) {
+        let (z_mode, epsilon) = (config.z_mode, config.z_blend_epsilon);
+        let depth = self.zbuffer[index];
+        let stored_bias = self.biases.get(index).map_or(0.0, |&bias| bias);
+        let nearer = ooz + self.bias - (depth + stored_bias);
+        let blends = depth > 0.0 && nearer.abs() < epsilon;
+
+        if let Some(overdraw) = &mut self.overdraw {
+            let plotted = z_mode == ZMode::Painter || blends || nearer > 0.0;
+            if depth > 0.0 && plotted {
+                overdraw[index] += 1;
+            }
+        }
+
+        if z_mode == ZMode::Painter {
+            self.keep_depth(index, ooz);
+            self.luminance[index] = l;
+            self.output[index] = color;
+        } else if blends {
+            // Close enough to what's already plotted that neither sample clearly wins, so mix
+            // them, favouring whichever is nearer.
+            let t = smoothstep(-as_f32(epsilon), as_f32(epsilon), as_f32(nearer));
+            self.output[index] = lerp_color(self.output[index], color, t);
+            self.luminance[index] += (l - self.luminance[index]) * t;
+            if nearer > 0.0 {
+                self.keep_depth(index, ooz);
+            }
+        } else if nearer > 0.0 {
+            self.keep_depth(index, ooz);
+            self.luminance[index] = l;
+            self.output[index] = color;
+        }
+    }
+
+    // Put a plotted sample's real depth in the z-buffer, remembering the bias it was tested with
+    fn keep_depth(&mut self, index: usize, ooz: Depth) {
+        self.zbuffer[index] = ooz;
+        if let Some(bias) = self.biases.get_mut(index) {
+            *bias = self.bias;
+        }
+    }
+}
+
+// Pick the surface color at (u, v), both 0..1, if something other than the gradient is coloring
+// it. `aspect` is how many times longer the surface is along v than along u, so checker squares come
+// out roughly square.
+fn albedo(config: &RenderConfig, u: f32, v: f32, aspect: f32) -> Option<u32> {
+    if let Some(texture) = &config.texture {
+        Some(texture.sample(u, v))
+    } else if let Some(density) = config.checker {
+        let u = (u * density as f32) as usize;
+        let v = (v * (density as f32 * aspect).round()) as usize;
+
+        Some(CHECKER_COLORS[(u + v) % 2])
+    } else {
+        None
+    }
+}
+
+// Where a point on a shape, relative to its center, lies in view space with the center at `offset`
+// from the middle of the view
+fn view_point(offset: [f32; 3], [x, y, z]: [f32; 3]) -> [f32; 3] {
+    [x + offset[0], y + offset[1], K2 + z + offset[2]]
+}
+
+// Where a point on a shape, relative to its center at `offset`, lands on screen, and its depth for
+// the z-buffer. Nothing comes nearer than `RenderConfig::z_near`.
+fn project_sample(
+    config: &RenderConfig,
+    k1: f32,
+    offset: [f32; 3],
+    point: [f32; 3],
+) -> (Depth, f32, f32) {
+    // The depth adds up how far away the point is at its own precision, so shapes a hair apart
+    // still come out in the right order if the z-buffer can tell them apart
+    let depth_z = as_depth(K2) + as_depth(offset[2]) + as_depth(point[2]);
+    let depth_z = depth_z.max(as_depth(config.z_near));
+
+    // Melting drags the bottom half of the shape down, more the lower it is, and more in some
+    // places than others so it looks like it's dripping.
+    let [x, y, z] = point;
+    let y = if config.melt > 0.0 && y < 0.0 {
+        let (sin, _) = sin_cos(x * MELT_DRIP_FREQUENCY);
+        let drip = 1.0 + MELT_DRIP * sin;
+        y - config.melt * MELT_DEPTH * y * y * drip
+    } else {
+        y
+    };
+    let [x, y, z] = view_point(offset, [x, y, z]);
+    let z = z.max(config.z_near);
+
+    // The x and y projection, then nudged across the screen
+    let (ooz, xp, yp) = project(x, y, z, k1, config.width, config.height);
+    let (xp, yp) = (xp + config.screen_offset[0], yp + config.screen_offset[1]);
+
+    // Zoom the cropped part of the screen out to fill all of it
+    let (xp, yp) = match config.crop {
+        Some([left, top, width, height]) => (
+            (xp - left) * config.width as f32 / width,
+            (yp - top) * config.height as f32 / height,
+        ),
+        None => (xp, yp),
+    };
+
+    // What the z-buffer compares, at its own precision
+    let depth = if cfg!(feature = "f64-depth") {
+        1.0 / depth_z
+    } else {
+        as_depth(ooz)
+    };
+
+    (depth, xp, yp)
+}
+
+// Shade a point on the surface, already rotated but relative to the shape's center at `offset`, and
+// plot it. `albedo` is only looked up for points facing the light.
+#[allow(clippy::too_many_arguments)]
+fn plot_sample(
+    config: &RenderConfig,
+    target: &mut Target,
+    k1: f32,
+    disc: &[(isize, isize)],
+    offset: [f32; 3],
+    point: [f32; 3],
+    [nx, ny, nz]: [f32; 3],
+    albedo: impl FnOnce() -> Option<u32>,
+) {
+    let (width, height) = (config.width, config.height);
+    let (point, brightness) = target.reflect(config, point);
+    if brightness <= 0.0 {
+        return;
+    }
+
+    let (depth, xp, yp) = project_sample(config, k1, offset, point);
+
+    // Calculate luminance: how directly the surface faces the light
+    let [lx, ly, lz] = config.light;
+    let l = nx * lx + ny * ly + nz * lz;
+
+    // l ranges from -1 to +1. If it's < 0, the surface is pointing away from the light, so we
+    // won't bother trying to plot it (or wherever it's below the cull threshold, if that's been
+    // changed). Anything plotted facing away is shaded as if it were edge-on.
+    if l <= config.cull_threshold {
+        return;
+    }
+
+    // Leave out the ragged band right along the edge of the shadow
+    if l.abs() < config.terminator_deadzone {
+        return;
+    }
+    let l = l.max(0.0);
+
+    if let Some(density) = &mut target.density {
+        let (x, y) = (xp.floor() as isize, yp.floor() as isize);
+        if x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height {
+            density[xy(width, x as usize, y as usize)] += 1;
+        }
+        return;
+    }
+
+    // Bend the shading curve: above 1 darkens the midtones, below 1 brightens them
+    let l = if config.shading_gamma == 1.0 {
+        l
+    } else {
+        l.powf(config.shading_gamma)
+    };
+
+    // Stretch the contrast window out to the whole range
+    let l = if config.lum_floor == 0.0 && config.lum_ceil == 1.0 {
+        l
+    } else {
+        let window = (config.lum_ceil - config.lum_floor).max(f32::EPSILON);
+        ((l - config.lum_floor) / window).clamp(0.0, 1.0)
+    };
+
+    // Catch the glint, sharply brighter the closer the surface faces its way
+    let l = if config.sparkle > 0.0 {
+        let [sx, sy, sz] = config.sparkle_direction;
+        let angle = (nx * sx + ny * sy + nz * sz).clamp(-1.0, 1.0).acos();
+        let glint = (1.0 - angle / SPARKLE_SIZE).max(0.0);
+        (l + config.sparkle * glint * glint).min(1.0)
+    } else {
+        l
+    };
+
+    // Flatten to the middle of whichever band it falls in
+    let l = match config.cel {
+        Some(bands) => (cel_band(l, bands) as f32 + 0.5) / bands as f32,
+        None => l,
+    };
+
+    // Convert the luminance_index into the range 0..11 (8 * sqrt(2) = 11.3), or a proportionally
+    // longer or shorter range for palettes with more or fewer colors than the gradient. A single
+    // color palette always gives index 0 and flat shading.
+    let palette = &config.palette;
+    let scale = palette.len() as f32 / GRADIENT.len() as f32;
+    let luminance_index = l * 8.0 * std::f32::consts::SQRT_2 * scale;
+
+    let color = match (albedo(), &config.cosine_palette) {
+        (Some(albedo), _) => lerp_color(0, albedo, l),
+
+        // Work the color out straight from the luminance, scrolling the same fraction of the way
+        // along as it would through the palette
+        (None, Some(cosine)) => {
+            let scroll = config.gradient_scroll / palette.len() as f32;
+            cosine.sample((l + scroll).rem_euclid(1.0))
+        }
+
+        // Now we lookup the color corresponding to the luminance
+        (None, None) if config.gradient_scroll == 0.0 => {
+            palette[(luminance_index as usize).min(palette.len() - 1)]
+        }
+
+        // ...or further along it, wrapping around from the brightest back to the darkest
+        (None, None) => {
+            let index = (luminance_index + config.gradient_scroll).rem_euclid(palette.len() as f32);
+            palette[(index as usize).min(palette.len() - 1)]
+        }
+    };
+    let (l, color) = target.dim(brightness, l, color);
+
+    if let (Some(accumulation), Some(radius)) = (&mut target.splat, config.splat) {
+        splat(
+            config,
+            target.zbuffer,
+            accumulation,
+            radius,
+            (xp, yp),
+            depth,
+            color,
+        );
+        return;
+    }
+
+    if let Some(fine) = &mut target.fine {
+        fine.plot(config, (xp, yp), (depth, target.bias), l, color);
+        return;
+    }
+
+    for &(dx, dy) in disc {
+        let x = xp.floor() as isize + dx;
+        let y = yp.floor() as isize + dy;
+
+        // K1 is derived from the width alone, so on a short screen (or with several donuts, or
+        // surfaces clamped to `z_near`) points can also project past the edges.
+        let on_screen = x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height;
+        let in_field = !config.interlace || y as usize % 2 == config.interlace_field;
+
+        if on_screen && in_field {
+            // ...and plot it in our output
+            target.plot(config, xy(width, x as usize, y as usize), depth, l, color);
+        }
+    }
+}
+
+// Draw a hair `length` long sticking straight out of the surface at `point`, unless the surface
+// faces away from the viewer. Hairs are shaded by depth, nearest brightest, rather than by the
+// light.
+#[allow(clippy::too_many_arguments)]
+fn plot_fur(
+    config: &RenderConfig,
+    target: &mut Target,
+    k1: f32,
+    length: f32,
+    reach: f32,
+    offset: [f32; 3],
+    point: [f32; 3],
+    normal: [f32; 3],
+) {
+    let [x, y, z] = view_point(offset, point);
+    let [nx, ny, nz] = normal;
+    if nx * x + ny * y + nz * z >= 0.0 {
+        return;
+    }
+
+    let [x, y, z] = point;
+    let tip = [x + nx * length, y + ny * length, z + nz * length];
+    plot_line(config, target, k1, offset, point, tip, |depth| {
+        let nearness = ((K2 + reach - 1.0 / as_f32(depth)) / (2.0 * reach)).clamp(0.0, 1.0);
+        let palette = &config.palette;
+        let color = match &config.cosine_palette {
+            Some(cosine) => cosine.sample(nearness),
+            None => palette[((nearness * palette.len() as f32) as usize).min(palette.len() - 1)],
+        };
+        (nearness, color)
+    });
+}
+
+// Draw a line between two points relative to a shape's center at `offset`, z-tested like any other
+// sample. `shade` gives the luminance and color to plot at each depth along it.
+fn plot_line(
+    config: &RenderConfig,
+    target: &mut Target,
+    k1: f32,
+    offset: [f32; 3],
+    from: [f32; 3],
+    to: [f32; 3],
+    shade: impl Fn(Depth) -> (f32, u32),
+) {
+    let (from, from_brightness) = target.reflect(config, from);
+    let (to, to_brightness) = target.reflect(config, to);
+    let (from_depth, x0, y0) = project_sample(config, k1, offset, from);
+    let (to_depth, x1, y1) = project_sample(config, k1, offset, to);
+
+    let (width, height) = (config.width, config.height);
+    let from = (x0.floor() as isize, y0.floor() as isize);
+    let to = (x1.floor() as isize, y1.floor() as isize);
+    line(from, to, |x, y, t| {
+        let on_screen = x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height;
+        let in_field = !config.interlace || y as usize % 2 == config.interlace_field;
+        if !on_screen || !in_field {
+            return;
+        }
+
+        // 1/z is linear across the screen, so it can be interpolated along the line directly
+        let depth = from_depth + (to_depth - from_depth) * as_depth(t);
+        let brightness = from_brightness + (to_brightness - from_brightness) * t;
+        if brightness <= 0.0 {
+            return;
+        }
+        let (l, color) = shade(depth);
+        let (l, color) = target.dim(brightness, l, color);
+
+        target.plot(config, xy(width, x as usize, y as usize), depth, l, color);
+    });
+}
+
+// The colors of the x, y, and z axes, and the bounding box, drawn by `RenderConfig::debug_axes`
+const AXIS_COLORS: [u32; 3] = [0xff0000, 0x00ff00, 0x0000ff];
+const BOUNDING_BOX_COLOR: u32 = 0xffffff;
+
+// How far the axes stick out past the donut, in world units
+const AXIS_OVERHANG: f32 = 0.5;
+
+// Draw the donut's own axes out from its center, a little longer than it is wide, and the box
+// around it, turned and placed the same way as the donut.
+fn plot_axes(config: &RenderConfig, a: f32, b: f32, offset: [f32; 3], target: &mut Target) {
+    let k1 = k1(config);
+    let (a, b) = (sin_cos(a), sin_cos(b));
+    let place = |point| rotate(point, a, b);
+
+    let reach = config.r1 + config.r2;
+    for (axis, &color) in AXIS_COLORS.iter().enumerate() {
+        let mut tip = [0.0; 3];
+        tip[axis] = reach + AXIS_OVERHANG;
+        plot_line(config, target, k1, offset, [0.0; 3], place(tip), |_| {
+            (1.0, color)
+        });
+    }
+
+    // The torus is revolved around the y-axis, so only the tube pokes up and down. The edges go
+    // between corners which differ in one coordinate.
+    let half = [reach, config.r1, reach];
+    let corner = |i: usize| {
+        let sign = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+        place([sign(1) * half[0], sign(2) * half[1], sign(4) * half[2]])
+    };
+    for i in 0..8 {
+        for bit in [1, 2, 4] {
+            if i & bit == 0 {
+                plot_line(
+                    config,
+                    target,
+                    k1,
+                    offset,
+                    corner(i),
+                    corner(i | bit),
+                    |_| (1.0, BOUNDING_BOX_COLOR),
+                );
+            }
+        }
+    }
+}
+
+// `RenderConfig::cage` draws this many circles around the tube, spaced out around the ring, and
+// this many around the ring, spaced out around the tube, each in this many straight segments.
+const CAGE_SPOKES: usize = 16;
+const CAGE_RINGS: usize = 8;
+const CAGE_SEGMENTS: usize = 48;
+
+// How far out from the surface the cage sits, in world units, so it isn't hidden by the very
+// surface it's drawn on
+const CAGE_LIFT: f32 = 0.05;
+
+// Draw the torus's wireframe, shaped and placed like `render_donut` draws the surface (apart from
+// exploding, which would need a cage per band). The surface in shadow isn't in the z-buffer to
+// hide lines behind it, so lines on parts of the tube facing away from the viewer are left out.
+fn plot_cage(
+    config: &RenderConfig,
+    a: f32,
+    b: f32,
+    offset: [f32; 3],
+    color: u32,
+    target: &mut Target,
+) {
+    let k1 = k1(config);
+    let (a, b) = (sin_cos(a), sin_cos(b));
+
+    let r1 = config.r1 + config.r2 * config.morph + CAGE_LIFT;
+    let r2 = config.r2 * (1.0 - config.morph);
+    let point = |theta: f32, phi: f32| {
+        let (sintheta, costheta) = sin_cos(theta);
+        let (sinphi, cosphi) = sin_cos(phi);
+        let circlex = r2 + r1 * costheta;
+        let point = rotate([circlex * cosphi, r1 * sintheta, circlex * sinphi], a, b);
+        let normal = rotate([costheta * cosphi, sintheta, costheta * sinphi], a, b);
+        let (point, [nx, ny, nz]) = stretch(config, point, normal);
+        let [x, y, z] = view_point(offset, point);
+        (point, nx * x + ny * y + nz * z < 0.0)
+    };
+    let mut line = |(from, facing), (to, _)| {
+        if facing {
+            plot_line(config, target, k1, offset, from, to, |_| (1.0, color));
+        }
+    };
+
+    let step = 2.0 * PI / CAGE_SEGMENTS as f32;
+    for spoke in 0..CAGE_SPOKES {
+        let phi = spoke as f32 * 2.0 * PI / CAGE_SPOKES as f32;
+        for segment in 0..CAGE_SEGMENTS {
+            let theta = segment as f32 * step;
+            line(point(theta, phi), point(theta + step, phi));
+        }
+    }
+    for ring in 0..CAGE_RINGS {
+        let theta = ring as f32 * 2.0 * PI / CAGE_RINGS as f32;
+        for segment in 0..CAGE_SEGMENTS {
+            let phi = segment as f32 * step;
+            line(point(theta, phi), point(theta, phi + step));
+        }
+    }
+}
+
+// Bresenham's line from `from` to `to`, both included, calling `plot` with each pixel and how far
+// along the line it is from 0 to 1.
+fn line(from: (isize, isize), to: (isize, isize), mut plot: impl FnMut(isize, isize, f32)) {
+    let (mut x, mut y) = from;
+    let (dx, dy) = ((to.0 - x).abs(), -(to.1 - y).abs());
+    let (sx, sy) = ((to.0 - x).signum(), (to.1 - y).signum());
+    let steps = dx.max(-dy).max(1) as f32;
+    let mut error = dx + dy;
+
+    for step in 0.. {
+        plot(x, y, step as f32 / steps);
+        if (x, y) == to {
+            break;
+        }
+
+        let e2 = 2 * error;
+        if e2 >= dy {
+            error += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            error += dx;
+            y += sy;
+        }
+    }
+}
+
+// Render whichever shape the config asks for
+fn render_shape(config: &RenderConfig, a: f32, b: f32, offset: [f32; 3], target: &mut Target) {
+    assert!(
+        !config.palette.is_empty(),
+        "the palette needs at least one color"
+    );
+    // NaN fails these too, and a spacing of 0 would never get all the way round
+    assert!(
+        config.theta_spacing > 0.0 && config.phi_spacing > 0.0,
+        "the sample spacings need to be above 0"
+    );
+    assert!(config.z_near > 0.0, "z_near needs to be above 0");
+
+    match config.shape {
+        Shape::Torus => render_donut(config, a, b, offset, target),
+        Shape::Cylinder { radius, height } => {
+            render_cylinder(config, radius, height, a, b, offset, target)
+        }
+    }
+}
+
+// How far below its center the lowest point of the shape is in view space, when rotated by `a` and
+// `b`. Both shapes are symmetrical around their y-axis, so it only depends on how far that's
+// tipped over: the circle around it reaches down by its radius times the sine of the tilt, plus
+// whatever's above and below that circle.
+fn lowest_point(config: &RenderConfig, a: f32, b: f32) -> f32 {
+    let [_, up, _] = rotate([0.0, 1.0, 0.0], sin_cos(a), sin_cos(b));
+    let tilt = (1.0 - up * up).max(0.0).sqrt();
+
+    let depth = match config.shape {
+        Shape::Torus => {
+            let r1 = config.r1 + config.r2 * config.morph;
+            let r2 = config.r2 * (1.0 - config.morph);
+            r2 * tilt + r1
+        }
+        Shape::Cylinder { radius, height } => radius * tilt + height / 2.0 * up.abs(),
+    };
+
+    depth * config.scale[1].abs()
+}
+
+// Render a single donut into a target which may already hold other objects. `offset` translates
+// the donut in world space after it has been rotated.
+fn render_donut(config: &RenderConfig, a: f32, b: f32, offset: [f32; 3], target: &mut Target) {
+    let k1 = k1(config);
+
+    // Precompute sines and cosines of a and b
+    let (sin_a, cos_a) = sin_cos(a);
+    let (sin_b, cos_b) = sin_cos(b);
+
+    // The pixels around each sample's projected position to fill in
+    let disc = disc(config.point_size);
+
+    // Morphing into a sphere shrinks the radius of revolution to nothing while the tube grows to
+    // make up the difference, so the overall size stays the same. Once the circle is centered on
+    // the axis, revolving it sweeps out a sphere (twice over). The normals are the same circle
+    // points however far through the morph we are, so they need no interpolating.
+    let r1 = config.r1 - target.inset + config.r2 * config.morph;
+    let r2 = config.r2 * (1.0 - config.morph);
+
+    // Theta goes around the cross-sectional circle of a torus
+    let mut theta = 0.0;
+    let mut theta_step = 0;
+    while theta < 2.0 * PI {
+        // Precompute sines and cosines of theta
+        let (sintheta, costheta) = sin_cos(theta);
+
+        // Which band of an exploded tube this slice belongs to, and how far that band has been
+        // pushed out along the direction of its center.
+        let segments = config.explode_segments.max(1);
+        let segment = ((theta / (2.0 * PI) * segments as f32) as usize).min(segments - 1);
+        let segment_theta = (segment as f32 + 0.5) * 2.0 * PI / segments as f32;
+        let (sin_segment, cos_segment) = sin_cos(segment_theta);
+        let explodex = config.explode_distance * cos_segment;
+        let explodey = config.explode_distance * sin_segment;
+
+        // Phi goes around the center of revolution of a torus
+        let mut phi = 0.0;
+        let mut phi_step = 0;
+        while phi < 2.0 * PI {
+            // Precompute sines and cosines of phi
+            let (sinphi, cosphi) = sin_cos(phi);
+
+            // The x,y coordinate of the circle, before revolving (factored out of the above
+            // equations)
+            let circlex = r2 + r1 * costheta + explodex;
+            let circley = r1 * sintheta + explodey;
+
+            // Final 3D (x,y,z) coordinate after rotations, directly from our math above, still
+            // centered on the donut
+            let x = circlex * (cos_b * cosphi + sin_a * sin_b * sinphi) - circley * cos_a * sin_b;
+            let y = circlex * (sin_b * cosphi - sin_a * cos_b * sinphi) + circley * cos_a * cos_b;
+            let z = cos_a * circlex * sinphi + circley * sin_a;
+
+            // The surface normal is the unit circle point (costheta, sintheta) put through the same
+            // rotations as the surface point itself.
+            let nx =
+                costheta * (cos_b * cosphi + sin_a * sin_b * sinphi) - sintheta * cos_a * sin_b;
+            let ny =
+                costheta * (sin_b * cosphi - sin_a * cos_b * sinphi) + sintheta * cos_a * cos_b;
+            let nz = cos_a * costheta * sinphi + sintheta * sin_a;
+
+            let ([x, y, z], [nx, ny, nz]) = stretch(config, [x, y, z], [nx, ny, nz]);
+
+            // The revolution is around r2 / r1 times longer than the tube, or about as long on a
+            // sphere. Without a texture or checkerboard, the side of the tube facing the hole
+            // (where costheta < 0) can have a different color to the outside.
+            let aspect = (r2 / r1).max(1.0);
+            plot_sample(
+                config,
+                target,
+                k1,
+                &disc,
+                offset,
+                [x, y, z],
+                [nx, ny, nz],
+                || {
+                    albedo(config, theta / (2.0 * PI), phi / (2.0 * PI), aspect).or(
+                        if costheta < 0.0 {
+                            config.inner_color
+                        } else {
+                            config.outer_color
+                        },
+                    )
+                },
+            );
+
+            if let Some(length) = config.fur {
+                if theta_step % FUR_THETA_STEPS == 0 && phi_step % FUR_PHI_STEPS == 0 {
+                    let reach = r1 + r2 + length;
+                    let (point, normal) = ([x, y, z], [nx, ny, nz]);
+                    plot_fur(config, target, k1, length, reach, offset, point, normal);
+                }
+            }
+
+            phi += config.phi_spacing;
+            phi_step += 1;
+        }
+
+        theta += config.theta_spacing;
+        theta_step += 1;
+    }
+}
+
+// Stretch a point on the surface by `RenderConfig::scale` about the shape's center, in view space.
+// Normals are scaled by the inverse instead, which keeps them perpendicular to the stretched
+// surface.
+fn stretch(config: &RenderConfig, point: [f32; 3], normal: [f32; 3]) -> ([f32; 3], [f32; 3]) {
+    if config.scale == [1.0; 3] {
+        return (point, normal);
+    }
+
+    let [sx, sy, sz] = config.scale;
+    let [x, y, z] = point;
+    let [nx, ny, nz] = [normal[0] / sx, normal[1] / sy, normal[2] / sz];
+    let length = (nx * nx + ny * ny + nz * nz).sqrt();
+
+    (
+        [x * sx, y * sy, z * sz],
+        [nx / length, ny / length, nz / length],
+    )
+}
+
+// The rotations the torus math above applies to a point on the circle swept around the y-axis,
+// for shapes which give their points in object space instead. Rotates by a around the x-axis, then
+// b around the z-axis.
+fn rotate([x, y, z]: [f32; 3], (sin_a, cos_a): (f32, f32), (sin_b, cos_b): (f32, f32)) -> [f32; 3] {
+    [
+        cos_b * x - cos_a * sin_b * y + sin_a * sin_b * z,
+        sin_b * x + cos_a * cos_b * y - sin_a * cos_b * z,
+        sin_a * y + cos_a * z,
+    ]
+}
+
+// Render a capped cylinder standing along the y-axis, in the same way as the donut: the side wall
+// and the two caps are sampled separately, about CYLINDER_SPACING apart in each direction.
+fn render_cylinder(
+    config: &RenderConfig,
+    radius: f32,
+    height: f32,
+    a: f32,
+    b: f32,
+    offset: [f32; 3],
+    target: &mut Target,
+) {
+    let k1 = k1(config);
+    let a = sin_cos(a);
+    let b = sin_cos(b);
+    let disc = disc(config.point_size);
+
+    let mut plot = |point: [f32; 3], normal: [f32; 3], u: f32, v: f32, aspect: f32| {
+        let point = rotate(point, a, b);
+        let normal = rotate(normal, a, b);
+        let (point, normal) = stretch(config, point, normal);
+
+        plot_sample(config, target, k1, &disc, offset, point, normal, || {
+            albedo(config, u, v, aspect)
+        });
+    };
+
+    let angle_spacing = CYLINDER_SPACING / radius;
+    let circumference = 2.0 * PI * radius;
+
+    // The side wall: the normal points straight out from the axis
+    let mut angle = 0.0;
+    while angle < 2.0 * PI {
+        let (sin, cos) = sin_cos(angle);
+
+        let mut y = -height / 2.0;
+        while y < height / 2.0 {
+            let v = y / height + 0.5;
+            plot(
+                [radius * cos, y, radius * sin],
+                [cos, 0.0, sin],
+                angle / (2.0 * PI),
+                v,
+                height / circumference,
+            );
+
+            y += CYLINDER_SPACING;
+        }
+
+        angle += angle_spacing;
+    }
+
+    // The caps: rings out from the axis, with fewer samples around the smaller rings
+    let mut r = CYLINDER_SPACING / 2.0;
+    while r < radius {
+        let mut angle = 0.0;
+        while angle < 2.0 * PI {
+            let (sin, cos) = sin_cos(angle);
+            let u = angle / (2.0 * PI);
+            let v = r / radius;
+
+            for side in [-1.0, 1.0] {
+                plot(
+                    [r * cos, side * height / 2.0, r * sin],
+                    [0.0, side, 0.0],
+                    u,
+                    v,
+                    radius / circumference,
+                );
+            }
+
+            angle += CYLINDER_SPACING / r;
+        }
+
+        r += CYLINDER_SPACING;
+    }
+}
+
+/// Render a frame into a newly allocated buffer of tightly packed RGBA8 pixels, `width * height * 4`
+/// bytes long, in row-major order starting from the top-left. Each pixel is the four bytes red,
+/// green, blue, alpha. Pixels covered by the donut are fully opaque, the background is fully
+/// transparent black. Panics if either size is 0, as [`RenderConfig::new`] does.
+pub fn render_frame_rgba(a: f32, b: f32, width: usize, height: usize) -> Vec<u8> {
+    let mut output = vec![0; width * height];
+    render_frame(&RenderConfig::new(width, height), a, b, &mut output);
+
+    output
+        .iter()
+        .flat_map(|&pixel| {
+            let alpha = if pixel == 0 { 0 } else { 0xff };
+            [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8, alpha]
+        })
+        .collect()
+}
+
+/// Render just the shading of a frame into a newly allocated `width * height` buffer, in
+/// row-major order starting from the top-left: the linear luminance of the nearest surface at each
+/// pixel, from 0 (edge-on to the light) to 1 (facing it), with no colors or gradient steps.
+/// Pixels the donut doesn't cover are NaN.
+pub fn render_luminance(a: f32, b: f32, width: usize, height: usize) -> Vec<f32> {
+    let mut output = vec![0; width * height];
+    let mut luminance = vec![f32::NAN; width * height];
+    render_frame_luminance(
+        &RenderConfig::new(width, height),
+        a,
+        b,
+        &mut output,
+        &mut luminance,
+    );
+
+    luminance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Anything the renderer leaves alone stays this color
+    const BACKGROUND: u32 = 0x123456;
+
+    // The default look, sampled more coarsely (though still without gaps at 100x100) so it renders
+    // quickly
+    fn quick_config() -> RenderConfig {
+        let mut config = RenderConfig::new(100, 100);
+        config.theta_spacing = 0.02;
+        config.phi_spacing = 0.007;
+        config
+    }
+
+    fn render(config: &RenderConfig) -> Vec<u32> {
+        let mut output = vec![BACKGROUND; config.width * config.height];
+        render_frame(config, 1.0, 0.5, &mut output);
+        output
+    }
+
+    // The box (min_x, min_y, max_x, max_y) around the pixels where `keep` is true
+    fn bounds(width: usize, keep: impl Fn(usize) -> bool) -> (usize, usize, usize, usize) {
+        let pixels: Vec<(usize, usize)> = (0..width * width)
+            .filter(|&i| keep(i))
+            .map(|i| (i % width, i / width))
+            .collect();
+        assert!(!pixels.is_empty());
+
+        let xs = pixels.iter().map(|&(x, _)| x);
+        let ys = pixels.iter().map(|&(_, y)| y);
+        (
+            xs.clone().min().unwrap(),
+            ys.clone().min().unwrap(),
+            xs.max().unwrap(),
+            ys.max().unwrap(),
+        )
+    }
+
+    // The fixed-point trig and projection are the only parts of the renderer which differ from the
+    // floating-point one, and agree with it to within the trade-offs in fixed.rs: 5e-5 for sines
+    // and cosines (a few steps of 1/65536, which both the angle and result are rounded to), a
+    // hundredth of a pixel on screen, and 1/65536 in 1/z.
+    #[test]
+    fn fixed_point_matches_floating_point() {
+        for i in 0..1000 {
+            let angle = i as f32 * 0.0131 - 6.5;
+            let (sin, cos) = float::sin_cos(angle);
+            let (fixed_sin, fixed_cos) = fixed::sin_cos(angle);
+            assert!((sin - fixed_sin).abs() < 5e-5 && (cos - fixed_cos).abs() < 5e-5);
+        }
+
+        let k1 = k1(&RenderConfig::new(200, 200));
+        for i in 0..1000 {
+            let (x, y) = ((i % 37) as f32 * 0.17 - 3.0, (i % 23) as f32 * 0.27 - 3.0);
+            let z = K2 - 3.0 + (i % 11) as f32 * 0.6;
+            let (ooz, xp, yp) = float::project(x, y, z, k1, 200, 200);
+            let (fixed_ooz, fixed_xp, fixed_yp) = fixed::project(x, y, z, k1, 200, 200);
+            assert!((ooz - fixed_ooz).abs() <= 1.0 / 65536.0);
+            assert!((xp - fixed_xp).abs() < 0.01 && (yp - fixed_yp).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn frames_are_the_same_every_time() {
+        let config = RenderConfig::new(100, 100);
+        assert!(render(&config) == render(&config));
+    }
+
+    const EASINGS: [Easing; 5] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+        Easing::Bounce,
+    ];
+
+    #[test]
+    fn easings_start_at_0_and_end_at_1() {
+        for kind in EASINGS {
+            assert!(ease(0.0, kind).abs() < 1e-6, "{:?}", kind);
+            assert!((ease(1.0, kind) - 1.0).abs() < 1e-6, "{:?}", kind);
+        }
+    }
+
+    // Bouncing dips back down between bounces, so it only stays within 0 to 1
+    #[test]
+    fn easings_only_go_up() {
+        for kind in EASINGS {
+            let values: Vec<f32> = (0..=100).map(|i| ease(i as f32 / 100.0, kind)).collect();
+            if kind == Easing::Bounce {
+                assert!(values.iter().all(|&v| (0.0..=1.0 + 1e-6).contains(&v)));
+            } else {
+                assert!(values.windows(2).all(|w| w[0] <= w[1]), "{:?}", kind);
+            }
+        }
+    }
+
+    #[test]
+    fn surfaces_nearer_than_z_near_are_clamped_to_it() {
+        // Stretched towards the viewer, the front of the donut is behind the camera, where 1/z
+        // would flip sign, and just in front of it, where it would blow up
+        let mut config = quick_config();
+        config.scale = [1.0, 1.0, 3.0];
+        for z_near in [1e-6, 0.5, 4.0] {
+            config.z_near = z_near;
+            let mut output = vec![BACKGROUND; 100 * 100];
+            let mut zbuffer = vec![0.0; 100 * 100];
+            render_frame_depth(&config, 1.0, 0.5, &mut output, &mut zbuffer);
+
+            let nearest = 1.0 / as_depth(z_near);
+            assert!(zbuffer
+                .iter()
+                .all(|&depth| depth.is_finite() && depth >= 0.0));
+            assert!(zbuffer.iter().all(|&depth| depth <= nearest * 1.001));
+            assert!(output.iter().any(|&pixel| pixel != BACKGROUND));
+        }
+    }
+
+    #[test]
+    fn solid_texture_is_shaded_by_the_light() {
+        let color = 0xc08040;
+        let mut config = quick_config();
+        config.texture = Some(Texture {
+            width: 2,
+            height: 2,
+            pixels: vec![color; 4],
+        });
+        let mut output = vec![BACKGROUND; 100 * 100];
+        let mut luminance = vec![f32::NAN; 100 * 100];
+        render_frame_luminance(&config, 1.0, 0.5, &mut output, &mut luminance);
+
+        let mut shades = Vec::new();
+        for (&pixel, &l) in output.iter().zip(&luminance) {
+            if !l.is_nan() {
+                assert_eq!(pixel, lerp_color(0, color, l));
+                shades.push(pixel);
+            }
+        }
+        shades.sort_unstable();
+        shades.dedup();
+        assert!(shades.len() > 10);
+
+        // Past the seams it tiles
+        let texture = Texture {
+            width: 2,
+            height: 2,
+            pixels: vec![0x000001, 0x000002, 0x000003, 0x000004],
+        };
+        assert_eq!(texture.sample(0.25, 0.75), 0x000003);
+        assert_eq!(texture.sample(1.25, -0.25), 0x000003);
+        assert_eq!(texture.sample(-1e-9, 0.0), 0x000002);
+    }
+
+    #[test]
+    fn shading_gamma_above_1_shades_darker() {
+        // The average position along the gradient of the colors drawn
+        let mean_index = |gamma| {
+            let mut config = quick_config();
+            config.shading_gamma = gamma;
+            let indices: Vec<usize> = render(&config)
+                .into_iter()
+                .filter(|&pixel| pixel != BACKGROUND)
+                .map(|pixel| GRADIENT.iter().position(|&color| color == pixel).unwrap())
+                .collect();
+            indices.iter().sum::<usize>() as f32 / indices.len() as f32
+        };
+
+        assert!(mean_index(2.0) < mean_index(1.0) - 1.0);
+        assert!(mean_index(0.5) > mean_index(1.0) + 1.0);
+    }
+
+    #[test]
+    fn anim_state_round_trips_through_json() {
+        let mut state = AnimState {
+            a: 1.25,
+            b: -0.1,
+            frame: 41,
+        };
+        state.step(1.0);
+
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(serde_json::from_str::<AnimState>(&json).unwrap(), state);
+        assert_eq!(state.frame, 42);
+    }
+
+    #[test]
+    fn interlacing_only_redraws_one_field() {
+        // The whole frame at `a`, drawn over `output`
+        let full = |a, output: &[u32]| {
+            let mut output = output.to_vec();
+            render_frame(&quick_config(), a, 0.5, &mut output);
+            output
+        };
+        let mut config = quick_config();
+        config.interlace = true;
+
+        // Drawing the even rows over one pose, then the odd rows over another
+        let first = full(1.0, &[BACKGROUND; 100 * 100]);
+        let mut output = first.clone();
+        config.interlace_field = 0;
+        render_frame(&config, 2.0, 0.5, &mut output);
+        let second = output.clone();
+        config.interlace_field = 1;
+        render_frame(&config, 3.0, 0.5, &mut output);
+
+        // Each row is what the whole frame would have been for the pose it was last drawn in,
+        // nearest surfaces in front, and the other field's rows are left as they were
+        let (even, odd) = (full(2.0, &first), full(3.0, &second));
+        assert!(even != odd);
+        for (y, row) in output.chunks(100).enumerate() {
+            let rows = y * 100..(y + 1) * 100;
+            if y % 2 == 0 {
+                assert!(second[rows.clone()] == even[rows.clone()], "row {}", y);
+                assert!(*row == second[rows], "row {}", y);
+            } else {
+                assert!(second[rows.clone()] == first[rows.clone()], "row {}", y);
+                assert!(*row == odd[rows], "row {}", y);
+            }
+        }
+    }
+
+    #[test]
+    fn anaglyph_eyes_see_the_donut_shifted_sideways() {
+        let mut config = quick_config();
+        config.anaglyph = Some(0.6);
+        let mut output = vec![0; 100 * 100];
+        render_frame(&config, 1.0, 0.5, &mut output);
+
+        // The left eye's view is in red and the right's in green and blue. Moving an eye to the
+        // left moves the donut to the right, the nearer the surface the further.
+        let (left_min, left_top, left_max, left_bottom) = bounds(100, |i| output[i] >> 16 != 0);
+        let (right_min, right_top, right_max, right_bottom) =
+            bounds(100, |i| output[i] & 0xffff != 0);
+        assert!(left_min > right_min && left_max > right_max);
+        assert!(left_min - right_min < 15 && left_max - right_max < 15);
+        assert_eq!((left_top, left_bottom), (right_top, right_bottom));
+    }
+
+    #[test]
+    fn on_frame_sees_and_can_change_the_rendered_frame() {
+        let config = quick_config();
+        let mut output = vec![BACKGROUND; 100 * 100];
+        let mut seen = Vec::new();
+        render_frame_with(&config, 1.0, 0.5, &mut output, |frame| {
+            seen = frame.to_vec();
+            frame[0] = 0xff00ff;
+        });
+
+        let mut expected = render(&config);
+        assert!(seen == expected);
+        expected[0] = 0xff00ff;
+        assert!(output == expected);
+    }
+
+    #[test]
+    fn inner_and_outer_colors_both_show() {
+        let mut config = quick_config();
+        config.inner_color = Some(0xff0000);
+        config.outer_color = Some(0x0000ff);
+        let drawn: Vec<u32> = render(&config)
+            .into_iter()
+            .filter(|&pixel| pixel != BACKGROUND && pixel != 0)
+            .collect();
+
+        // Each a shade of one or the other
+        assert!(drawn
+            .iter()
+            .all(|&pixel| pixel & 0x00ffff == 0 || pixel & 0xffff00 == 0));
+        assert!(drawn.iter().any(|&pixel| pixel > 0x00ffff));
+        assert!(drawn.iter().any(|&pixel| pixel <= 0x0000ff));
+    }
+
+    #[test]
+    fn morph_goes_from_the_torus_to_a_sphere() {
+        let mut config = quick_config();
+        let torus = render(&config);
+        config.morph = 1.0;
+        let morphed = render(&config);
+
+        config.morph = 0.0;
+        assert!(render(&config) == torus);
+
+        // A torus with nothing in the middle of its tube is a sphere: round, and solid through the
+        // middle where the donut has its hole
+        config.r1 += config.r2;
+        config.r2 = 0.0;
+        assert!(morphed == render(&config));
+        let (min_x, min_y, max_x, max_y) = bounds(100, |i| morphed[i] != BACKGROUND);
+        assert!((max_x - min_x).abs_diff(max_y - min_y) <= 1);
+        assert!(morphed[xy(100, (min_x + max_x) / 2, (min_y + max_y) / 2)] != BACKGROUND);
+        assert!(torus != morphed);
+    }
+
+    #[test]
+    fn hit_test_finds_the_tube_but_not_the_hole() {
+        // Face on, the tube goes round the middle of the screen a quarter of the way out
+        let config = quick_config();
+        assert!(is_donut_pixel(&config, 75, 50, PI / 2.0, 0.0));
+        assert!(is_donut_pixel(&config, 25, 50, PI / 2.0, 0.0));
+        assert!(!is_donut_pixel(&config, 50, 50, PI / 2.0, 0.0));
+        assert!(!is_donut_pixel(&config, 0, 0, PI / 2.0, 0.0));
+        assert!(!is_donut_pixel(&config, 100, 50, PI / 2.0, 0.0));
+
+        // And it agrees with what's drawn
+        let mut output = vec![BACKGROUND; 100 * 100];
+        render_frame(&config, 1.0, 0.5, &mut output);
+        for i in (0..100 * 100).step_by(499) {
+            let drawn = output[i] != BACKGROUND;
+            assert_eq!(is_donut_pixel(&config, i % 100, i / 100, 1.0, 0.5), drawn);
+        }
+    }
+
+    #[test]
+    fn coarse_sampling_at_high_resolution_warns() {
+        let mut config = RenderConfig::new(100, 100);
+        assert!(check_sampling_density(&config).is_none());
+
+        // Ten times as far apart over ten times as many pixels across
+        config = RenderConfig::new(1000, 1000);
+        config.theta_spacing = 0.07;
+        config.phi_spacing = 0.02;
+        let warning = check_sampling_density(&config).unwrap();
+        assert!(warning.spacing > 1.0);
+
+        // ...which bigger points cover
+        config.point_size = warning.spacing.ceil() as usize;
+        assert!(check_sampling_density(&config).is_none());
+    }
+
+    #[test]
+    fn silhouette_only_draws_the_edge() {
+        let mut config = quick_config();
+        let drawn = render(&config);
+        config.silhouette = Some(0xff0000);
+        let outline = render(&config);
+
+        let covered = |x: usize, y: usize| drawn[xy(100, x, y)] != BACKGROUND;
+        let uncovered = |x: usize, y: usize| x < 100 && y < 100 && !covered(x, y);
+        let mut interior = 0;
+        for y in 0..100usize {
+            for x in 0..100usize {
+                let edge = uncovered(x.wrapping_sub(1), y)
+                    || uncovered(x + 1, y)
+                    || uncovered(x, y.wrapping_sub(1))
+                    || uncovered(x, y + 1);
+                let expected = if covered(x, y) && edge {
+                    0xff0000
+                } else {
+                    interior += covered(x, y) as usize;
+                    BACKGROUND
+                };
+                assert_eq!(outline[xy(100, x, y)], expected, "({}, {})", x, y);
+            }
+        }
+        assert!(interior > 100);
+    }
+
+    #[test]
+    fn painter_mode_lets_farther_surfaces_drawn_later_win() {
+        // Tipped so the tube passes in front of itself
+        let draw = |z_mode| {
+            let mut config = quick_config();
+            config.z_mode = z_mode;
+            let mut output = vec![BACKGROUND; 100 * 100];
+            let mut zbuffer = vec![0.0; 100 * 100];
+            render_frame_depth(&config, 0.3, 0.5, &mut output, &mut zbuffer);
+            (output, zbuffer)
+        };
+        let (depth, depth_z) = draw(ZMode::Depth);
+        let (painter, painter_z) = draw(ZMode::Painter);
+
+        // The same pixels are covered, but where the tube overlaps itself the depth test keeps the
+        // nearest surface while the painter keeps whichever came last
+        let covered = |frame: &[u32]| frame.iter().map(|&p| p != BACKGROUND).collect::<Vec<_>>();
+        assert_eq!(covered(&depth), covered(&painter));
+        assert!(depth_z
+            .iter()
+            .zip(&painter_z)
+            .all(|(depth, painter)| depth >= painter));
+        let hidden = depth_z
+            .iter()
+            .zip(&painter_z)
+            .filter(|(d, p)| d > p)
+            .count();
+        assert!(hidden > 50, "{}", hidden);
+        assert!(depth != painter);
+    }
+
+    #[test]
+    fn cel_shades_in_n_bands_and_the_outline() {
+        let mut config = quick_config();
+        for bands in 2..=5 {
+            config.cel = Some(bands);
+            let mut colors: Vec<u32> = render(&config)
+                .into_iter()
+                .filter(|&pixel| pixel != BACKGROUND)
+                .collect();
+            colors.sort_unstable();
+            colors.dedup();
+
+            assert_eq!(colors.len(), bands + 1, "{:x?}", colors);
+            assert!(colors.contains(&CEL_OUTLINE));
+        }
+    }
+
+    #[test]
+    fn fur_sticks_out_past_the_silhouette() {
+        let mut config = quick_config();
+        let bare = render(&config);
+        config.fur = Some(0.4);
+        let furry = render(&config);
+
+        let covered = |frame: &[u32], i: usize| frame[i] != BACKGROUND;
+        assert!((0..100 * 100).all(|i| !covered(&bare, i) || covered(&furry, i)));
+        let (bare_left, bare_top, bare_right, bare_bottom) = bounds(100, |i| covered(&bare, i));
+        let (left, top, right, bottom) = bounds(100, |i| covered(&furry, i));
+        assert!(left < bare_left && top < bare_top);
+        assert!(right > bare_right && bottom > bare_bottom);
+    }
+
+    #[test]
+    fn scale_stretches_the_silhouette_and_keeps_the_shading() {
+        // Face on, so the donut's height and width on screen are its size across
+        let frame = |scale| {
+            let mut config = quick_config();
+            config.scale = scale;
+            let mut output = vec![BACKGROUND; 100 * 100];
+            render_frame(&config, PI / 2.0, 0.0, &mut output);
+            output
+        };
+        let size = |frame: &[u32]| {
+            let (left, top, right, bottom) = bounds(100, |i| frame[i] != BACKGROUND);
+            ((right - left) as f32, (bottom - top) as f32)
+        };
+        let (width, height) = size(&frame([1.0; 3]));
+        let egg = frame([1.0, 1.3, 1.0]);
+        let (egg_width, egg_height) = size(&egg);
+
+        assert!((egg_width - width).abs() <= 1.0);
+        assert!(
+            (egg_height / height - 1.3).abs() < 0.05,
+            "{} {}",
+            height,
+            egg_height
+        );
+
+        // Still shaded from dark to bright through the gradient, the normals having been
+        // stretched to match
+        let mut shades: Vec<usize> = egg
+            .iter()
+            .filter(|&&pixel| pixel != BACKGROUND)
+            .map(|pixel| GRADIENT.iter().position(|color| color == pixel).unwrap())
+            .collect();
+        shades.sort_unstable();
+        shades.dedup();
+        assert!(shades.len() >= GRADIENT.len() - 2, "{:?}", shades);
+    }
+
+    #[test]
+    fn debug_axes_start_at_the_projected_origin() {
+        // Tipped towards face on and turned a little, so the origin shows through the hole and every
+        // axis goes some way across the screen
+        let mut config = quick_config();
+        config.debug_axes = true;
+        let mut output = vec![BACKGROUND; 100 * 100];
+        render_frame(&config, 1.1, 0.3, &mut output);
+
+        let (_, origin_x, origin_y) = project_sample(&config, k1(&config), [0.0; 3], [0.0; 3]);
+        for color in AXIS_COLORS {
+            let nearest = (0..100 * 100)
+                .filter(|&i| output[i] == color)
+                .map(|i| {
+                    let (dx, dy) = ((i % 100) as f32 - origin_x, (i / 100) as f32 - origin_y);
+                    (dx * dx + dy * dy).sqrt()
+                })
+                .fold(f32::INFINITY, f32::min);
+            assert!(nearest < 1.5, "{:06x} is {} away", color, nearest);
+        }
+    }
+
+    #[test]
+    fn reflection_shows_below_the_donut_fading_with_distance() {
+        let mut config = quick_config();
+        config.width = 100;
+        config.height = 200;
+        let draw = |config: &RenderConfig| {
+            let mut output = vec![BACKGROUND; 100 * 200];
+            render_frame(config, 1.0, 0.5, &mut output);
+            output
+        };
+        let donut = draw(&config);
+        config.reflection = 0.6;
+        let reflected = draw(&config);
+
+        // Everything added is under the donut
+        let bottom = (0..100 * 200)
+            .filter(|&i| donut[i] != BACKGROUND)
+            .max()
+            .unwrap()
+            / 100;
+        let rows: Vec<Vec<f32>> = (0..200)
+            .map(|y| {
+                (0..100)
+                    .map(|x| xy(100, x, y))
+                    .filter(|&i| reflected[i] != donut[i])
+                    .map(|i| post::brightness(reflected[i]))
+                    .collect()
+            })
+            .collect();
+        assert!(rows[..bottom - 2].iter().all(|row| row.is_empty()));
+
+        // ...dimmer than the donut, and dimmer still the further down it goes
+        let brightest = |frame: &[u32]| {
+            frame
+                .iter()
+                .map(|&pixel| post::brightness(pixel))
+                .fold(0.0, f32::max)
+        };
+        let drawn: Vec<f32> = rows[bottom..].iter().flatten().cloned().collect();
+        assert!(!drawn.is_empty());
+        assert!(drawn.iter().cloned().fold(0.0, f32::max) < 0.7 * brightest(&donut));
+        let mean = |rows: &[Vec<f32>]| {
+            let all: Vec<f32> = rows.iter().flatten().cloned().collect();
+            all.iter().sum::<f32>() / all.len() as f32
+        };
+        let reflected_rows: Vec<Vec<f32>> = rows[bottom..]
+            .iter()
+            .filter(|row| !row.is_empty())
+            .cloned()
+            .collect();
+        let third = reflected_rows.len() / 3;
+        assert!(mean(&reflected_rows[..third]) > 1.5 * mean(&reflected_rows[2 * third..]));
+    }
+
+    #[test]
+    fn gradient_scroll_moves_each_pixel_along_the_gradient() {
+        let index = |pixel: &u32| GRADIENT.iter().position(|color| color == pixel);
+        let mut config = quick_config();
+        let still = render(&config);
+
+        // Whole colors at a time, each pixel moves that many along, wrapping back to the darkest
+        for scroll in [1.0, 3.0, 11.0] {
+            config.gradient_scroll = scroll;
+            let scrolled = render(&config);
+            assert!(scrolled != still);
+            for (pixel, scrolled) in still.iter().zip(&scrolled) {
+                let expected = index(pixel).map(|i| (i + scroll as usize) % GRADIENT.len());
+                assert_eq!(index(scrolled), expected);
+            }
+        }
+
+        config.gradient_scroll = 0.0;
+        assert!(render(&config) == still);
+    }
+
+    #[test]
+    fn depth_stats_count_the_pixels_drawn() {
+        for (a, b) in [(1.0, 0.5), (0.3, 2.0), (PI / 2.0, 0.0)] {
+            let config = quick_config();
+            let mut output = vec![BACKGROUND; 100 * 100];
+            let mut zbuffer = vec![0.0; 100 * 100];
+            render_frame_depth(&config, a, b, &mut output, &mut zbuffer);
+
+            let stats = depth_stats(&zbuffer);
+            let drawn = output.iter().filter(|&&pixel| pixel != BACKGROUND).count();
+            assert_eq!(stats.covered, drawn);
+
+            // Somewhere between the back and front of the donut, in 1/z
+            let (r1, r2) = (config.r1, config.r2);
+            assert!(1.0 / (K2 + r1 + r2) <= stats.min * 1.001 && stats.min <= stats.mean);
+            assert!(stats.mean <= stats.max && stats.max * 0.999 <= 1.0 / (K2 - r1 - r2));
+        }
+
+        assert_eq!(depth_stats(&[0.0; 4]).covered, 0);
+    }
+
+    #[test]
+    fn narrower_luminance_windows_push_shades_to_the_extremes() {
+        // The fraction of the drawn pixels in the darkest or brightest color
+        let extremes = |floor, ceil| {
+            let mut config = quick_config();
+            config.lum_floor = floor;
+            config.lum_ceil = ceil;
+            let drawn: Vec<u32> = render(&config)
+                .into_iter()
+                .filter(|&pixel| pixel != BACKGROUND)
+                .collect();
+            let ends = [GRADIENT[0], GRADIENT[GRADIENT.len() - 1]];
+            drawn.iter().filter(|pixel| ends.contains(pixel)).count() as f32 / drawn.len() as f32
+        };
+
+        let usual = extremes(0.0, 1.0);
+        let narrow = extremes(0.3, 0.6);
+        assert!(narrow > usual + 0.3, "{} {}", usual, narrow);
+        assert!(extremes(0.45, 0.46) > 0.95);
+    }
+
+    #[test]
+    fn cosine_palette_samples_the_closed_form_at_both_ends() {
+        let palettes = [
+            CosinePalette {
+                a: [0.5; 3],
+                b: [0.5; 3],
+                c: [1.0; 3],
+                d: [0.0, 0.33, 0.67],
+            },
+            CosinePalette {
+                a: [0.8, 0.5, 0.4],
+                b: [0.2, 0.4, 0.7],
+                c: [0.5, 1.0, 2.5],
+                d: [0.25, 0.1, 0.0],
+            },
+        ];
+        for palette in palettes {
+            for t in [0.0, 1.0] {
+                let sampled = palette.sample(t);
+                for i in 0..3 {
+                    let angle = 2.0 * PI * (palette.c[i] * t + palette.d[i]);
+                    let value = (palette.a[i] + palette.b[i] * angle.cos()).clamp(0.0, 1.0);
+                    let channel = (sampled >> (16 - 8 * i)) & 0xff;
+                    assert!(channel.abs_diff((value * 255.0).round() as u32) <= 1);
+                }
+            }
+        }
+
+        // Which for the rainbow are full red, and a little green and blue
+        assert_eq!(palettes[0].sample(0.0), palettes[0].sample(1.0));
+        assert_eq!(palettes[0].sample(0.0) >> 16, 0xff);
+    }
+
+    // What `debug_density` (or with `overdraw`, `debug_overdraw`) counted for each pixel, and the
+    // z-buffer left behind
+    fn counted(config: &RenderConfig, overdraw: bool) -> (Vec<u32>, Vec<Depth>) {
+        let size = config.width * config.height;
+        let (mut zbuffer, mut luminance, mut output) =
+            (vec![0.0; size], vec![0.0; size], vec![0; size]);
+        let mut counts = vec![0; size];
+        let mut target = Target::new(&mut zbuffer, &mut luminance, &mut output);
+        if overdraw {
+            target.overdraw = Some(&mut counts);
+        } else {
+            target.density = Some(&mut counts);
+        }
+        render_shape(config, 1.0, 0.5, [0.0; 3], &mut target);
+        (counts, zbuffer)
+    }
+
+    #[test]
+    fn density_counts_every_lit_sample_on_screen() {
+        // The painter plots every lit sample, and every one after the first on a pixel counts as
+        // drawing over it
+        let mut config = quick_config();
+        let (density, _) = counted(&config, false);
+        config.z_mode = ZMode::Painter;
+        let (overdraw, zbuffer) = counted(&config, true);
+        let covered = zbuffer.iter().filter(|&&depth| depth > 0.0).count() as u32;
+
+        assert_eq!(
+            density.iter().sum::<u32>(),
+            overdraw.iter().sum::<u32>() + covered
+        );
+        assert!(density
+            .iter()
+            .zip(&zbuffer)
+            .all(|(&count, &depth)| (count > 0) == (depth > 0.0)));
+    }
+
+    #[test]
+    fn overdraw_counts_z_tests_passed_after_the_first_on_each_pixel() {
+        // Samples at made up depths all over 10 pixels, counting which pass the z-test as they go
+        let config = quick_config();
+        let (mut zbuffer, mut luminance, mut output) = (vec![0.0; 10], vec![0.0; 10], vec![0; 10]);
+        let mut overdraw = vec![0; 10];
+        let mut target = Target {
+            overdraw: Some(&mut overdraw),
+            ..Target::new(&mut zbuffer, &mut luminance, &mut output)
+        };
+        let (mut nearest, mut passed) = ([0.0; 10], 0);
+        for i in 0..1000u32 {
+            let index = (i * 7 % 10) as usize;
+            let ooz = as_depth((i.wrapping_mul(2_654_435_761) % 1000 + 1) as f32 / 1000.0);
+            if ooz > nearest[index] {
+                nearest[index] = ooz;
+                passed += 1;
+            }
+            target.plot(&config, index, ooz, 0.5, 0xffffff);
+        }
+
+        let touched = nearest.iter().filter(|&&depth| depth > 0.0).count();
+        assert_eq!(touched, 10);
+        assert!(passed > 2 * touched);
+        assert_eq!(overdraw.iter().sum::<u32>() as usize, passed - touched);
+    }
+
+    #[test]
+    fn cage_is_drawn_over_the_shaded_surface() {
+        let mut config = quick_config();
+        let bare = render(&config);
+        config.cage = Some(0xff00ff);
+        let caged = render(&config);
+
+        let cage = caged.iter().filter(|&&pixel| pixel == 0xff00ff).count();
+        let shaded = caged
+            .iter()
+            .filter(|pixel| GRADIENT.contains(pixel))
+            .count();
+        assert!(cage > 200 && shaded > 2000, "{} {}", cage, shaded);
+
+        // The wires sit just off the surface, so most of them are over the donut, and
+        // everywhere else it's shaded as usual
+        let over = (0..100 * 100)
+            .filter(|&i| caged[i] == 0xff00ff && bare[i] != BACKGROUND)
+            .count();
+        assert!(over * 4 > cage * 3, "{} of {}", over, cage);
+        assert!((0..100 * 100)
+            .filter(|&i| caged[i] != 0xff00ff && bare[i] != BACKGROUND)
+            .all(|i| GRADIENT.contains(&caged[i])));
+    }
+
+    #[test]
+    fn cull_threshold_trades_coverage() {
+        let mut config = quick_config();
+        let usual = covered(&config);
+        config.cull_threshold = 0.3;
+        let brightest = covered(&config);
+        config.cull_threshold = -0.3;
+        let fuller = covered(&config);
+        assert!(
+            brightest < usual && usual < fuller,
+            "{} {} {}",
+            brightest,
+            usual,
+            fuller
+        );
+    }
+
+    #[test]
+    fn sparkle_moves_between_frames() {
+        let mut config = quick_config();
+        let bare = render(&config);
+        config.sparkle = 1.0;
+
+        // The pixels the glint brightened on each frame
+        let mut glint = |frame| {
+            config.sparkle_direction = sparkle_direction(frame);
+            let sparkled = render(&config);
+            (0..100 * 100)
+                .filter(|&i| sparkled[i] != bare[i])
+                .collect::<Vec<_>>()
+        };
+        let (first, later) = (glint(0), glint(40));
+        assert!(!first.is_empty() && !later.is_empty());
+        assert_ne!(first, later);
+
+        // And with no sparkle the donut's as it was
+        config.sparkle = 0.0;
+        config.sparkle_direction = sparkle_direction(40);
+        assert_eq!(render(&config), bare);
+    }
+
+    #[cfg(feature = "window-tests")]
+    #[test]
+    fn draws_into_the_callers_window() {
+        let config = quick_config();
+        let mut window = minifb::Window::new(
+            "Donut",
+            config.width,
+            config.height,
+            minifb::WindowOptions::default(),
+        )
+        .unwrap();
+
+        // The frame goes into the window it's given, which is still open afterwards
+        draw_into_window(&mut window, &config, 1.0, 0.5).unwrap();
+        draw_into_window(&mut window, &config, 1.1, 0.5).unwrap();
+        assert!(window.is_open());
+    }
+
+    #[test]
+    fn ring_draws_each_donut_apart() {
+        let config = quick_config();
+        let mut output = vec![BACKGROUND; 100 * 100];
+        // Wide enough apart that none of them overlap, even squashed by the wheel's tilt
+        render_ring(&config, 5, 20.0, 1.0, 0.5, &mut output);
+
+        // Flood fill each patch of donut, counting those big enough to be a whole one
+        let mut seen = vec![false; 100 * 100];
+        let mut donuts = 0;
+        for start in 0..100 * 100 {
+            if seen[start] || output[start] == BACKGROUND {
+                continue;
+            }
+            let (mut stack, mut size) = (vec![start], 0);
+            seen[start] = true;
+            while let Some(i) = stack.pop() {
+                size += 1;
+                let (x, y) = (i % 100, i / 100);
+                let around = [
+                    (x + 1, y),
+                    (x.wrapping_sub(1), y),
+                    (x, y + 1),
+                    (x, y.wrapping_sub(1)),
+                ];
+                for (x, y) in around.iter().filter(|&&(x, y)| x < 100 && y < 100) {
+                    let j = xy(100, *x, *y);
+                    if !seen[j] && output[j] != BACKGROUND {
+                        seen[j] = true;
+                        stack.push(j);
+                    }
+                }
+            }
+            if size > 20 {
+                donuts += 1;
+            }
+        }
+        assert_eq!(donuts, 5);
+    }
+
+    #[test]
+    fn z_bias_wins_ties_without_moving_the_depths() {
+        let mut red = quick_config();
+        red.palette = vec![0xff0000];
+        let mut green = red.clone();
+        green.palette = vec![0x00ff00];
+
+        // Two donuts in exactly the same place, the second treated as `bias` nearer
+        let coincident = |bias| {
+            let size = 100 * 100;
+            let (mut zbuffer, mut luminance, mut output) =
+                (vec![0.0; size], vec![0.0; size], vec![BACKGROUND; size]);
+            let mut target = Target::new(&mut zbuffer, &mut luminance, &mut output);
+            render_shape(&red, 1.0, 0.5, [0.0; 3], &mut target);
+            target.set_bias(bias);
+            render_shape(&green, 1.0, 0.5, [0.0; 3], &mut target);
+            (output, zbuffer)
+        };
+
+        // Left to the exact depths the first one drawn keeps every pixel, but biased the second
+        // takes them all, and the z-buffer holds the real depths either way
+        let unbiased = zbuffer(&red, 1.0, 0.5, [0.0; 3]);
+        for (bias, winner) in [(0.0, 0xff0000), (0.001, 0x00ff00)] {
+            let (output, zbuffer) = coincident(bias);
+            let drawn: Vec<u32> = output.into_iter().filter(|&c| c != BACKGROUND).collect();
+            assert!(drawn.len() > 2000);
+            assert!(drawn.iter().all(|&c| c == winner));
+            assert!(zbuffer == unbiased);
+        }
+    }
+
+    #[test]
+    fn z_supersampling_keeps_the_rim_off_whats_behind_it() {
+        // A red donut partly in front of a green one, sampled every `spacing` radians
+        let scene = |point_size, spacing: f32, factor| {
+            let mut red = quick_config();
+            red.point_size = point_size;
+            red.theta_spacing = spacing;
+            red.phi_spacing = spacing * 0.35;
+            red.z_supersample = factor;
+            red.palette = vec![0xff0000];
+            let mut green = red.clone();
+            green.palette = vec![0x00ff00];
+
+            let size = 100 * 100;
+            let (mut zbuffer, mut luminance, mut output) =
+                (vec![0.0; size], vec![0.0; size], vec![BACKGROUND; size]);
+            let mut fine = (factor > 1).then(|| Supersample::new(&red));
+            let mut target = Target {
+                fine: fine.as_mut(),
+                ..Target::new(&mut zbuffer, &mut luminance, &mut output)
+            };
+            render_shape(&green, 1.0, 0.5, [1.5, 0.0, 2.0], &mut target);
+            render_shape(&red, 1.0, 0.5, [-1.5, 0.0, -1.0], &mut target);
+            if let Some(fine) = target.fine.take() {
+                fine.resolve(&red, &mut target);
+            }
+            output
+        };
+
+        // Where the red donut's rim covers pixels which, sampled as finely as can be, are the
+        // green one's. Samples plotted as discs spread the rim out over them, but z-tested finely
+        // they don't.
+        let truth = scene(1, 0.005, 1);
+        let artifacts = |factor| {
+            let output = scene(3, 0.02, factor);
+            (0..100 * 100)
+                .filter(|&i| output[i] == 0xff0000 && truth[i] == 0x00ff00)
+                .count()
+        };
+        let (plain, supersampled) = (artifacts(1), artifacts(4));
+        assert!(
+            plain > 50 && supersampled * 10 < plain,
+            "{} {}",
+            plain,
+            supersampled
+        );
+    }
+
+    #[test]
+    fn orbiting_light_stays_put_on_the_donut() {
+        let dot = |[x, y, z]: [f32; 3], [lx, ly, lz]: [f32; 3]| x * lx + y * ly + z * lz;
+        let poses = [(0.0, 0.0), (1.0, 0.5), (2.5, -1.2), (4.0, 3.0)];
+
+        // However the donut's turned, each part of its surface is lit the same with the light
+        // orbiting along, but not with it standing still
+        let mut changes = false;
+        for i in 0..36 {
+            let (sin_theta, cos_theta) = (i as f32 * 0.7).sin_cos();
+            let (sin_phi, cos_phi) = (i as f32 * 0.3).sin_cos();
+            let normal = [cos_theta * cos_phi, sin_theta, -cos_theta * sin_phi];
+
+            let lit = dot(normal, LIGHT);
+            for &(a, b) in &poses {
+                let turned = rotate(normal, sin_cos(a), sin_cos(b));
+                assert!((dot(turned, orbit_light(LIGHT, a, b)) - lit).abs() < 1e-5);
+                changes |= (dot(turned, LIGHT) - lit).abs() > 0.1;
+            }
+        }
+        assert!(changes);
+    }
+
+    #[test]
+    fn terminator_deadzone_thins_the_darkest_shade() {
+        let darkest = |deadzone| {
+            let mut config = quick_config();
+            config.terminator_deadzone = deadzone;
+            render(&config)
+                .iter()
+                .filter(|&&pixel| pixel == GRADIENT[0])
+                .count()
+        };
+
+        // The darkest shade is where the surface is only just lit, so trimming the edge of the
+        // shadow takes a good part of it
+        let (usual, trimmed) = (darkest(0.0), darkest(0.05));
+        assert!(
+            usual > 50 && trimmed * 3 < usual * 2,
+            "{} {}",
+            usual,
+            trimmed
+        );
+    }
+
+    #[test]
+    fn proc_palette_drifts_smoothly_and_reproduces() {
+        assert!(proc_palette(7, 1.3) == proc_palette(7, 1.3));
+        assert!(proc_palette(7, 1.3) != proc_palette(8, 1.3));
+        assert!(proc_palette(7, 0.0) != proc_palette(7, 3.0));
+
+        // A frame's step along never changes any channel of any shade by much
+        let channel = |color: u32, shift: u32| ((color >> shift) & 0xff) as i32;
+        for frame in 0..600 {
+            let (t, next) = (frame as f32 / 300.0, (frame + 1) as f32 / 300.0);
+            let (before, after) = (proc_palette(7, t), proc_palette(7, next));
+            assert_eq!(before.len(), GRADIENT.len());
+            for (&before, &after) in before.iter().zip(&after) {
+                for shift in [16, 8, 0] {
+                    assert!((channel(before, shift) - channel(after, shift)).abs() <= 3);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shell_darkens_the_rim() {
+        let mut config = quick_config();
+        let solid = render(&config);
+        config.shell_thickness = 0.0;
+        assert!(render(&config) == solid);
+        config.shell_thickness = 0.2;
+        let shell = render(&config);
+
+        // The pixels at the edge of the silhouette, and those well inside it
+        let covered = |x: usize, y: usize| solid[xy(100, x, y)] != BACKGROUND;
+        let (mut rim, mut inside) = (Vec::new(), Vec::new());
+        for y in 2..98 {
+            for x in 2..98 {
+                let around = |reach: usize| {
+                    [
+                        (x - reach, y),
+                        (x + reach, y),
+                        (x, y - reach),
+                        (x, y + reach),
+                    ]
+                    .iter()
+                    .all(|&(x, y)| covered(x, y))
+                };
+                if !covered(x, y) {
+                    continue;
+                } else if !around(1) {
+                    rim.push(xy(100, x, y));
+                } else if around(2) {
+                    inside.push(xy(100, x, y));
+                }
+            }
+        }
+
+        // How much darker the shell makes them, on average
+        let darkening = |pixels: &[usize]| {
+            pixels
+                .iter()
+                .map(|&i| post::brightness(solid[i]) - post::brightness(shell[i]))
+                .sum::<f32>()
+                / pixels.len() as f32
+        };
+        let (rim, inside) = (darkening(&rim), darkening(&inside));
+        assert!(rim > 0.1 && rim > 2.0 * inside, "{} {}", rim, inside);
+    }
+
+    // Render with `palette`, returning every color drawn
+    fn colors_drawn(palette: Vec<u32>) -> Vec<u32> {
+        let mut config = RenderConfig::new(100, 100);
+        config.palette = palette;
+        let mut colors: Vec<u32> = render(&config)
+            .into_iter()
+            .filter(|&pixel| pixel != BACKGROUND)
+            .collect();
+        colors.sort_unstable();
+        colors.dedup();
+        colors
+    }
+
+    #[test]
+    fn mono_shades_from_near_black_up_to_the_color() {
+        let colors = colors_drawn(mono_palette(0xff8040, 12));
+        let brightest = colors
+            .iter()
+            .max_by(|a, b| post::brightness(**a).total_cmp(&post::brightness(**b)));
+        let dimmest = colors
+            .iter()
+            .min_by(|a, b| post::brightness(**a).total_cmp(&post::brightness(**b)));
+
+        assert_eq!(brightest, Some(&0xff8040));
+        assert!(post::brightness(*dimmest.unwrap()) < 0.1 * post::brightness(0xff8040));
+        assert!(colors
+            .iter()
+            .all(|&color| mono_palette(0xff8040, 12).contains(&color)));
+    }
+
+    #[test]
+    fn one_color_palette_shades_flat() {
+        assert_eq!(colors_drawn(vec![0xff8000]), [0xff8000]);
+    }
+
+    #[test]
+    fn two_color_palette_uses_both() {
+        assert_eq!(colors_drawn(vec![0x000080, 0xffffff]), [0x000080, 0xffffff]);
+    }
+
+    #[test]
+    fn large_palette_stays_in_bounds() {
+        let palette: Vec<u32> = (0..1000).map(|i| i * 0x010101 % 0x1000000 + 1).collect();
+        let colors = colors_drawn(palette.clone());
+        assert!(colors.len() > 100);
+        assert!(colors.iter().all(|color| palette.contains(color)));
+    }
+
+    #[test]
+    #[should_panic(expected = "the palette needs at least one color")]
+    fn empty_palette_is_an_error() {
+        colors_drawn(Vec::new());
+    }
+
+    #[test]
+    fn tiny_screens_render() {
+        for (width, height) in [(1, 1), (2, 2)] {
+            let config = RenderConfig::new(width, height);
+            assert_eq!(render(&config).len(), width * height);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "the screen needs to be at least 1x1 pixels, not 0x10")]
+    fn zero_width_is_an_error() {
+        RenderConfig::new(0, 10);
+    }
+
+    #[test]
+    fn crisp_outline_follows_auto_frame() {
+        for margin in [10.0, 30.0] {
+            let mut config = RenderConfig::new(100, 100);
+            config.auto_frame = Some(margin);
+            let plain = render(&config);
+            config.crisp_outline = true;
+            let outlined = render(&config);
+
+            let covered = bounds(100, |i| plain[i] != BACKGROUND);
+            let outline = bounds(100, |i| outlined[i] != plain[i]);
+            assert!(outline.0 + 1 >= covered.0 && outline.1 + 1 >= covered.1);
+            assert!(outline.2 <= covered.2 + 1 && outline.3 <= covered.3 + 1);
+        }
+    }
+
+    #[test]
+    fn crisp_outline_marks_where_the_tube_passes_in_front_of_itself() {
+        // Nearly edge on, so the near side of the ring hides the far side behind it
+        let mut config = quick_config();
+        let mut plain = vec![BACKGROUND; 100 * 100];
+        let mut zbuffer = vec![0.0; 100 * 100];
+        render_frame_depth(&config, 0.3, 0.5, &mut plain, &mut zbuffer);
+        config.crisp_outline = true;
+        let mut outlined = vec![BACKGROUND; 100 * 100];
+        render_frame(&config, 0.3, 0.5, &mut outlined);
+
+        // Pixels where the depth jumps to a neighbour, far enough inside the donut that outlining
+        // its edge wouldn't reach them or their neighbours
+        let within = |i: usize, reach: usize| {
+            let (x, y) = (i % 100, i / 100);
+            let xs = x.saturating_sub(reach)..(x + reach + 1).min(100);
+            (y.saturating_sub(reach)..(y + reach + 1).min(100))
+                .flat_map(move |y| xs.clone().map(move |x| y * 100 + x))
+        };
+        let neighbours = |i| within(i, 1);
+        let jumps: Vec<usize> = (0..100 * 100)
+            .filter(|&i| within(i, 2).all(|j| zbuffer[j] > 0.0))
+            .filter(|&i| {
+                let nearest = neighbours(i)
+                    .map(|j| as_f32(zbuffer[j]))
+                    .fold(0.0, f32::max);
+                let farthest = neighbours(i)
+                    .map(|j| as_f32(zbuffer[j]))
+                    .fold(1.0, f32::min);
+                nearest - farthest >= CRISP_EDGE_THRESHOLD * nearest
+            })
+            .collect();
+        assert!(!jumps.is_empty());
+
+        for i in jumps {
+            assert!(neighbours(i).any(|j| outlined[j] != plain[j]), "{}", i);
+        }
+    }
+
+    // Every pixel the donut covers, out of the whole frame
+    fn covered(config: &RenderConfig) -> usize {
+        render(config)
+            .iter()
+            .filter(|&&pixel| pixel != BACKGROUND)
+            .count()
+    }
+
+    #[test]
+    fn bigger_points_fill_more_pixels() {
+        // Sampled sparsely enough to leave gaps between single pixels
+        let mut config = RenderConfig::new(100, 100);
+        config.theta_spacing = 0.2;
+        config.phi_spacing = 0.1;
+
+        assert_eq!(disc(1), [(0, 0)]);
+        let counts: Vec<usize> = [1, 2, 3, 5]
+            .iter()
+            .map(|&size| {
+                config.point_size = size;
+                covered(&config)
+            })
+            .collect();
+        assert!(counts.windows(2).all(|w| w[0] < w[1]), "{:?}", counts);
+    }
+
+    #[test]
+    fn auto_tune_samples_closer_at_higher_resolutions() {
+        let tuned = |size| {
+            let mut config = RenderConfig::new(size, size);
+            auto_tune_spacing(&mut config);
+            assert!(check_sampling_density(&config).is_none());
+            (config.theta_spacing, config.phi_spacing)
+        };
+        let (low, high) = (tuned(100), tuned(800));
+
+        assert!(high.0 < low.0 && high.1 < low.1, "{:?} {:?}", low, high);
+    }
+
+    #[test]
+    fn auto_tune_keeps_cropped_coverage() {
+        let mut config = RenderConfig::new(100, 100);
+        config.crop = Some([25.0, 25.0, 50.0, 50.0]);
+        let before = covered(&config);
+
+        auto_tune_spacing(&mut config);
+        assert!(check_sampling_density(&config).is_none());
+        assert!(covered(&config) >= before * 99 / 100);
+    }
+
+    #[test]
+    fn auto_frame_fits_oversized_configs() {
+        let changes: [fn(&mut RenderConfig); 5] = [
+            |config| (config.r1, config.r2) = (1.5, 2.5),
+            |config| config.explode_distance = 0.75,
+            |config| config.fur = Some(0.5),
+            |config| config.scale = [1.4, 1.0, 1.0],
+            // Fully melted, the default donut's bounding sphere takes in the viewer, leaving nothing to frame
+            |config| config.melt = 0.5,
+        ];
+
+        for change in changes {
+            let mut config = quick_config();
+            config.auto_frame = Some(10.0);
+            change(&mut config);
+
+            // Turning the donut about both axes brings every part of it to the edge of the sphere
+            for step in 0..4 {
+                let angle = step as f32 * std::f32::consts::FRAC_PI_4;
+                let mut frame = vec![BACKGROUND; 100 * 100];
+                render_frame(&config, angle, angle, &mut frame);
+
+                let (min_x, min_y, max_x, max_y) = bounds(100, |i| frame[i] != BACKGROUND);
+                assert!(min_x >= 10 && min_y >= 10, "{:?}", (min_x, min_y));
+                assert!(max_x <= 90 && max_y <= 90, "{:?}", (max_x, max_y));
+            }
+        }
+    }
+
+    #[test]
+    fn splat_falls_off_and_averages_colors() {
+        let config = RenderConfig::new(9, 9);
+        let front = vec![1.0; 9 * 9];
+        let mut accumulation = vec![[0.0; 4]; 9 * 9];
+        splat(
+            &config,
+            &front,
+            &mut accumulation,
+            3.0,
+            (4.5, 4.5),
+            1.0,
+            0x804020,
+        );
+
+        // Softer further out, and nothing past the radius
+        let weight = |accumulation: &[[f32; 4]], x| accumulation[xy(9, x, 4)][3];
+        for x in 4..7 {
+            assert!(weight(&accumulation, x) > weight(&accumulation, x + 1));
+        }
+        assert_eq!(weight(&accumulation, 8), 0.0);
+
+        // However much of a sample lands on a pixel, dividing by the weight gives its color back,
+        // and where two overlap evenly it's halfway between them
+        let average = |[r, g, b, weight]: [f32; 4]| [r / weight, g / weight, b / weight];
+        for &sum in accumulation.iter().filter(|sum| sum[3] > 0.0) {
+            let [r, g, b] = average(sum);
+            assert!((r - 128.0).abs() < 1e-3 && (g - 64.0).abs() < 1e-3 && (b - 32.0).abs() < 1e-3);
+        }
+
+        splat(
+            &config,
+            &front,
+            &mut accumulation,
+            3.0,
+            (4.5, 4.5),
+            1.0,
+            0x000000,
+        );
+        for &sum in accumulation.iter().filter(|sum| sum[3] > 0.0) {
+            let [r, g, b] = average(sum);
+            assert!((r - 64.0).abs() < 1e-3 && (g - 32.0).abs() < 1e-3 && (b - 16.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn f64_depth_orders_nearly_coincident_donuts() {
+        let mut config = quick_config();
+        let mut zbuffer = vec![0.0; 100 * 100];
+        let mut luminance = vec![0.0; 100 * 100];
+        let mut output = vec![BACKGROUND; 100 * 100];
+
+        // The second donut is a hair nearer, too little for f32 to tell apart 5 units away, so the
+        // first one drawn keeps the pixels they tie on
+        let mut target = Target::new(&mut zbuffer, &mut luminance, &mut output);
+        config.palette = vec![0xff0000];
+        render_shape(&config, 1.0, 0.5, [0.0; 3], &mut target);
+        config.palette = vec![0x00ff00];
+        render_shape(&config, 1.0, 0.5, [0.0, 0.0, -1e-7], &mut target);
+
+        let far = output.iter().filter(|&&pixel| pixel == 0xff0000).count();
+        if cfg!(feature = "f64-depth") {
+            assert_eq!(far, 0);
+        } else {
+            assert!(far > 0);
+        }
+    }
+
+    #[test]
+    fn default_gradient_brightens_then_deepens() {
+        let gradient = default_gradient();
+        assert_eq!(gradient.len(), 12);
+
+        // Near enough darkest first up to the lightest yellow, only the violet at the very start
+        // being a shade brighter than the blue after it, then darker every step to the brown
+        let brightness: Vec<f32> = gradient
+            .iter()
+            .map(|&color| post::brightness(color))
+            .collect();
+        let peak = 7;
+        for pair in brightness[..=peak].windows(2) {
+            assert!(pair[1] > pair[0] - 0.03, "{:?}", brightness);
+        }
+        for pair in brightness[peak..].windows(2) {
+            assert!(pair[1] < pair[0], "{:?}", brightness);
+        }
+    }
+
+    #[test]
+    fn melting_only_drags_down_below_each_shapes_center() {
+        let mut config = RenderConfig::new(100, 100);
+        let k1 = k1(&config);
+
+        // Up at the top of a ring, the bottom of the donut is still above the middle of the view
+        let offset = [0.0, 2.0, 0.0];
+        let (above, below) = ([0.5, 0.5, 0.0], [0.5, -0.5, 0.0]);
+        let still = [above, below].map(|point| project_sample(&config, k1, offset, point));
+        config.melt = 1.0;
+        let melted = [above, below].map(|point| project_sample(&config, k1, offset, point));
+
+        assert_eq!(melted[0], still[0]);
+        assert_eq!(melted[1].1, still[1].1);
+        assert!(melted[1].2 > still[1].2);
+    }
+
+    // Pixels standing out from all four of their neighbours, brighter or darker than each of them
+    fn speckles(frame: &[u32]) -> usize {
+        let brightness = |x: usize, y: usize| post::brightness(frame[xy(100, x, y)]);
+        (1..99)
+            .flat_map(|y| (1..99).map(move |x| (x, y)))
+            .filter(|&(x, y)| {
+                let pixel = brightness(x, y);
+                let around = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                    .map(|(x, y)| brightness(x, y) - pixel);
+                around.iter().all(|&d| d > 0.05) || around.iter().all(|&d| d < -0.05)
+            })
+            .count()
+    }
+
+    #[test]
+    fn z_blending_smooths_out_speckle() {
+        // Two donuts in the same place, sampled a little differently, so which is nearer at each
+        // pixel comes down to where their samples happen to land
+        let fight = |epsilon| {
+            let mut config = quick_config();
+            config.z_blend_epsilon = epsilon;
+            let mut zbuffer = vec![0.0; 100 * 100];
+            let mut luminance = vec![0.0; 100 * 100];
+            let mut output = vec![BACKGROUND; 100 * 100];
+
+            let mut target = Target::new(&mut zbuffer, &mut luminance, &mut output);
+            config.palette = vec![0xff0000];
+            render_shape(&config, 1.0, 0.5, [0.0; 3], &mut target);
+            config.palette = vec![0x00ff00];
+            config.theta_spacing *= 1.1;
+            config.phi_spacing *= 1.1;
+            render_shape(&config, 1.0, 0.5, [0.0; 3], &mut target);
+            speckles(&output)
+        };
+
+        let (sharp, blended) = (fight(0.0), fight(0.005));
+        assert!(sharp > 100 && blended < sharp / 10, "{} {}", sharp, blended);
+    }
+
+    #[test]
+    fn projected_bounds_hold_the_rendered_donut() {
+        let config = quick_config();
+        let (min_x, min_y, max_x, max_y) = projected_bounds(&config);
+
+        // Turned every which way, the donut stays inside the box and reaches close to its sides
+        let mut reached = (usize::MAX, usize::MAX, 0, 0);
+        for step in 0..16 {
+            let (a, b) = (step as f32 * 0.4, step as f32 * 0.25);
+            let mut frame = vec![BACKGROUND; 100 * 100];
+            render_frame(&config, a, b, &mut frame);
+
+            let (left, top, right, bottom) = bounds(100, |i| frame[i] != BACKGROUND);
+            assert!(left as f32 >= min_x.floor() && top as f32 >= min_y.floor());
+            assert!(right as f32 <= max_x.floor() && bottom as f32 <= max_y.floor());
+            reached = (
+                reached.0.min(left),
+                reached.1.min(top),
+                reached.2.max(right),
+                reached.3.max(bottom),
+            );
+        }
+
+        assert!(reached.0 as f32 <= min_x + 1.5 && reached.2 as f32 >= max_x - 1.5);
+        assert!(reached.1 as f32 <= min_y + 1.5 && reached.3 as f32 >= max_y - 1.5);
+    }
+
+    #[test]
+    fn rgba_pixels_are_row_major_red_first() {
+        let (width, height) = (40, 30);
+        let rgba = render_frame_rgba(1.0, 0.5, width, height);
+        assert_eq!(rgba.len(), width * height * 4);
+
+        let colors = frame(&RenderConfig::new(width, height), 1.0, 0.5);
+        let covered = colors.iter().position(|&pixel| pixel != 0).unwrap();
+        let (x, y) = (covered % width, covered / width);
+        let [_, r, g, b] = colors[covered].to_be_bytes();
+        assert_eq!(rgba[(y * width + x) * 4..][..4], [r, g, b, 0xff]);
+        assert_eq!(rgba[..4], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn luminance_is_0_to_1_and_nan_off_the_donut() {
+        let luminance = render_luminance(1.0, 0.5, 60, 60);
+        let colors = frame(&RenderConfig::new(60, 60), 1.0, 0.5);
+        assert_eq!(luminance.len(), 60 * 60);
+
+        for (&l, &pixel) in luminance.iter().zip(&colors) {
+            if pixel == 0 {
+                assert!(l.is_nan());
+            } else {
+                assert!((0.0..=1.0).contains(&l), "{}", l);
+            }
+        }
+        assert!(luminance.iter().any(|&l| l > 0.9));
+    }
+
+    #[test]
+    fn bounce_turns_back_at_both_ends() {
+        let range = (-0.5, 1.0);
+        let folded: Vec<f32> = (0..400).map(|i| bounce(i as f32 * 0.01, range)).collect();
+        assert!(folded.iter().all(|&angle| (-0.5..=1.0).contains(&angle)));
+
+        // Rising from the start up to the top, falling to the bottom, then rising again
+        let turns: Vec<f32> = folded
+            .windows(3)
+            .filter(|w| (w[1] - w[0]) * (w[2] - w[1]) < 0.0)
+            .map(|w| w[1])
+            .collect();
+        assert_eq!(turns.len(), 2, "{:?}", turns);
+        assert!((turns[0] - 1.0).abs() < 0.01 && (turns[1] + 0.5).abs() < 0.01);
+        assert!(folded[1] > folded[0] && folded[399] > folded[398]);
+    }
+
+    #[test]
+    fn every_preset_renders() {
+        for (name, _) in PRESETS {
+            let mut config = preset(name, 100, 100).unwrap();
+            assert!(check_sampling_density(&config).is_none(), "{}", name);
+            for amount in [
+                config.reflection,
+                config.scanlines,
+                config.grain,
+                config.vignette,
+            ] {
+                assert!((0.0..=1.0).contains(&amount), "{}", name);
+            }
+
+            // Coarser, to keep the test quick
+            config.theta_spacing = 0.02;
+            config.phi_spacing = 0.007;
+            let frame = render(&config);
+            let mut colors: Vec<u32> = frame.into_iter().filter(|&p| p != BACKGROUND).collect();
+            colors.sort_unstable();
+            colors.dedup();
+            assert!(colors.len() > 1, "{}", name);
+        }
+
+        assert!(preset("nonesuch", 100, 100).is_none());
+    }
+}
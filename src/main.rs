@@ -1,158 +1,1615 @@
-// https://www.a1k0n.net/2011/07/20/donut-math.html
+mod args;
+mod audio;
+mod braille;
+mod fbdev;
+mod target;
 
-use minifb::{Scale, Window, WindowOptions};
+use donut::{
+    post, render_frame_depth, render_frame_luminance, render_row_depth, AnimState, Depth, Easing,
+    RenderConfig, Texture,
+};
+use minifb::Key;
 use std::f32::consts::PI;
-
-fn xy(width: usize, x: usize, y: usize) -> usize {
-    y * width + x
-}
-
-const GRADIENT: [u32; 12] = [
-    // 0x421e0f, // Brown 3
-    0x19071a, // Dark violett
-    0x09012f, // Darkest blue
-    0x040449, // Blue 5
-    0x000764, // Blue 4
-    // 0x0c2c8a, // Blue 3
-    // 0x1852b1, // Blue 2
-    0x397dd1, // Blue 1
-    0x86b5e5, // Blue 0
-    0xd3ecf8, // Lightest blue
-    0xf1e9bf, // Lightest yellow
-    0xf8c95f, // Light yellow
-    0xffaa00, // Dirty yellow
-    0xcc8000, // Brown 0
-    // 0x995700, // Brown 1
-    0x6a3403, // Brown 2
-];
+use std::io::{BufRead, Write};
+use target::{BrailleTarget, RecordingTarget, RenderTarget, ReplayTarget, WindowTarget};
 
 const SCREEN_WIDTH: usize = 100;
 const SCREEN_HEIGHT: usize = 100;
 
-const THETA_SPACING: f32 = 0.007;
-const PHI_SPACING: f32 = 0.002;
-
-// Inner torus radius
-const R1: f32 = 1.0;
-
-// Outer torus radius
-const R2: f32 = 2.0;
-
-// The distance of the donut from the viewer
-const K2: f32 = 5.0;
-
-// Calculate K1 based on screen size: The maximum x-distance occurs roughly at the edge of the
-// torus, which is at x=R1+R2, z=0.
-//
-// We want that to be displaced 3/8ths of the width of the screen, which is 3/4th of the way from
-// the center to the side of the screen.
-//
-// screen_width * 3/8 = K1 * (R1 + R2) / (K2 + 0)
-// screen_width * K2 *3 / (8 * (R1 + R2)) = K1
-const K1: f32 = SCREEN_WIDTH as f32 * K2 * 3.0 / (8.0 * (R1 + R2));
-
-fn render_frame(a: f32, b: f32, output: &mut [u32], output_xy: fn(usize, usize) -> usize) {
-    // Precompute sines and cosines of a and b
-    let cos_a: f32 = a.cos();
-    let sin_a: f32 = a.sin();
-    let cos_b: f32 = b.cos();
-    let sin_b: f32 = b.sin();
-
-    let mut zbuffer = vec![0.0; SCREEN_WIDTH * SCREEN_HEIGHT];
-    let zbuffer_xy = |x, y| xy(SCREEN_WIDTH, x, y);
-
-    // Theta goes around the cross-sectional circle of a torus
-    let mut theta = 0.0;
-    while theta < 2.0 * PI {
-        // Precompute sines and cosines of theta
-        let costheta = theta.cos();
-        let sintheta = theta.sin();
-
-        // Phi goes around the center of revolution of a torus
-        let mut phi = 0.0;
-        while phi < 2.0 * PI {
-            // Precompute sines and cosines of phi
-            let cosphi = phi.cos();
-            let sinphi = phi.sin();
-
-            // The x,y coordinate of the circle, before revolving (factored out of the above
-            // equations)
-            let circlex = R2 + R1 * costheta;
-            let circley = R1 * sintheta;
-
-            // Final 3D (x,y,z) coordinate after rotations, directly from our math above
-            let x = circlex * (cos_b * cosphi + sin_a * sin_b * sinphi) - circley * cos_a * sin_b;
-            let y = circlex * (sin_b * cosphi - sin_a * cos_b * sinphi) + circley * cos_a * cos_b;
-            let z = K2 + cos_a * circlex * sinphi + circley * sin_a;
-            let ooz = 1.0 / z; // "one over z"
-
-            // The x and y projection. Note that y is negated here, because y goes up in 3D space
-            // but down on 2D displays.
-            let xp = (SCREEN_WIDTH as f32 / 2.0 + K1 * ooz * x) as usize;
-            let yp = (SCREEN_HEIGHT as f32 / 2.0 - K1 * ooz * y) as usize;
-
-            // Calculate luminance. Ugly, but correct.
-            let l = cosphi * costheta * sin_b - cos_a * costheta * sinphi - sin_a * sintheta
-                + cos_b * (cos_a * sintheta - costheta * sin_a * sinphi);
-
-            // l ranges from -sqrt(2) to +sqrt(2). If it's < 0, the surface is pointing away from
-            // us, so we won't bother trying to plot it.
-            if l > 0.0 {
-                // Test against the z-buffer. larger 1/z means the pixel is closer to the viewer
-                // than what's already plotted.
-
-                if ooz > zbuffer[zbuffer_xy(xp, yp)] as f32 {
-                    zbuffer[zbuffer_xy(xp, yp)] = ooz;
-
-                    // Convert the luminance_index into the range 0..11 (8 * sqrt(2) = 11.3)
-                    let luminance_index = l * 8.0;
-
-                    // Now we lookup the color corresponding to the luminance and plot it in
-                    // our output:
-                    output[output_xy(xp, yp)] = GRADIENT[luminance_index as usize];
-                }
+// With the audio feature, how much the input level (0..1) speeds up the spin and brightens the
+// donut
+const AUDIO_SPIN: f32 = 8.0;
+const AUDIO_BRIGHTEN: f32 = 2.0;
+
+// How far the light moves each frame while one of the IJKL keys is held, in radians
+const LIGHT_STEP: f32 = 0.03;
+
+// How much the radii change each frame while one of the bracket, minus, or equals keys is held,
+// and how far they can go. Keeping R1 + R2 below K2 (5) keeps the whole donut in front of the
+// viewer.
+const RADIUS_STEP: f32 = 0.02;
+const R1_RANGE: (f32, f32) = (0.1, 2.0);
+const R2_RANGE: (f32, f32) = (0.0, 2.5);
+
+// With --explode, how far the pieces drift apart at the peak of the explosion, and how many frames
+// it takes to fly apart and come back together, by default
+const EXPLODE_AMPLITUDE: f32 = 0.75;
+const EXPLODE_PERIOD: u64 = 240;
+
+// Pressing X shakes the camera, starting this many pixels off center and falling off by this
+// factor each frame
+const SHAKE_AMPLITUDE: f32 = 4.0;
+const SHAKE_DECAY: f32 = 0.9;
+
+// With --ring, the radius of the circle the donuts go around by default, in world units
+const RING_RADIUS: f32 = 5.0;
+
+// With --anaglyph, how far apart the eyes are in world units
+const EYE_SEPARATION: f32 = 0.3;
+
+// With --mono, how many shades of the color there are between black and full brightness. As many
+// as the default gradient has colors, so the full color is used just as often as the gradient's
+// brightest.
+const MONO_SHADES: usize = 12;
+
+// How many frames switching palettes with P takes to fade from one to the next, by default
+const PALETTE_FADE_FRAMES: u64 = 30;
+
+// With --proc-palette, how many frames it takes to drift from one color scheme to the next, by
+// default
+const PROC_PALETTE_FRAMES: u64 = 300;
+
+// How many frames --spritesheet renders, and how many columns they're laid out in, by default
+const SPRITESHEET_FRAMES: usize = 16;
+const SPRITESHEET_COLUMNS: usize = 4;
+
+// How many frames a second --mp4 videos play at, the same as the window's frame rate limit
+const MP4_FPS: u32 = 60;
+
+// With --ascii-braille, how many times taller than wide the terminal's characters are
+const CHAR_ASPECT: f32 = 2.0;
+
+// With --crop, the samples are brought closer together by at most this much, however far it zooms
+// in. Each doubling takes four times as long to render, so past this gaps are the better deal.
+const MAX_CROP_ZOOM: f32 = 4.0;
+
+// The palettes P switches between, fading from one to the next. The first is whatever the options
+// are showing, which can change from frame to frame, so it's passed in each time rather than kept.
+struct PaletteCycle {
+    // The ones after the first
+    palettes: Vec<Vec<u32>>,
+    current: usize,
+    frames: u64,
+
+    // What was showing when the fade started, how many frames into it we are, and the palette
+    // given for the last frame, if it wasn't the first palette
+    from: Vec<u32>,
+    frame: u64,
+    showing: Option<Vec<u32>>,
+
+    easing: Easing,
+}
+
+impl PaletteCycle {
+    // Cycle from the palette we start with to the default gradient and plain white, skipping
+    // repeats
+    fn new(start: &[u32], frames: u64, easing: Easing) -> PaletteCycle {
+        let mut palettes: Vec<Vec<u32>> = Vec::new();
+        for palette in [
+            donut::default_gradient().to_vec(),
+            donut::mono_palette(0xffffff, MONO_SHADES),
+        ] {
+            if palette != start && !palettes.contains(&palette) {
+                palettes.push(palette);
             }
+        }
 
-            phi += PHI_SPACING;
+        PaletteCycle {
+            palettes,
+            current: 0,
+            frames: frames.max(1),
+            from: start.to_vec(),
+            frame: frames,
+            showing: None,
+            easing,
         }
+    }
+
+    // Start fading to the next palette from whatever's showing, `start` if it's the first
+    fn next(&mut self, start: &[u32]) {
+        self.current = (self.current + 1) % (self.palettes.len() + 1);
+        self.from = self.showing.take().unwrap_or_else(|| start.to_vec());
+        self.frame = 0;
+    }
+
+    // The palette for this frame, or None to leave the first palette, `start`, showing as it is
+    fn step(&mut self, start: &[u32]) -> Option<Vec<u32>> {
+        let to = match self.current {
+            0 => start,
+            i => &self.palettes[i - 1],
+        };
 
-        theta += THETA_SPACING;
+        self.showing = if self.frame < self.frames {
+            self.frame += 1;
+            let t = donut::ease(self.frame as f32 / self.frames as f32, self.easing);
+            Some(donut::blend_palettes(&self.from, to, t))
+        } else if self.current == 0 {
+            None
+        } else {
+            Some(to.to_vec())
+        };
+        self.showing.clone()
     }
 }
 
-fn main() {
-    let mut window = Window::new(
-        "Donut",
+// A cosine palette as a list of colors, as many as the default gradient, for fading to and from
+fn sample_cosine(cosine: &donut::CosinePalette) -> Vec<u32> {
+    let colors = donut::default_gradient().len();
+    (0..colors)
+        .map(|i| cosine.sample((i as f32 + 0.5) / colors as f32))
+        .collect()
+}
+
+// A camera shake which dies down over time
+struct Shake {
+    amplitude: f32,
+    decay: f32,
+    easing: Easing,
+
+    // How much of the shake is left, falling from 1 by the decay each frame. The easing shapes how
+    // that turns into the amplitude.
+    strength: f32,
+
+    // xorshift state, so the jitter is random-looking without pulling in a crate for it
+    seed: u32,
+}
+
+impl Shake {
+    // Start shaking again at full strength
+    fn start(&mut self, amplitude: f32) {
+        self.amplitude = amplitude;
+        self.strength = 1.0;
+    }
+
+    // How far to move the frame this time, in pixels in each direction
+    fn next_offset(&mut self) -> [f32; 2] {
+        if self.strength == 0.0 {
+            return [0.0; 2];
+        }
+
+        let mut random = || {
+            self.seed ^= self.seed << 13;
+            self.seed ^= self.seed >> 17;
+            self.seed ^= self.seed << 5;
+
+            // Map to -1..1
+            self.seed as f32 / u32::MAX as f32 * 2.0 - 1.0
+        };
+        let offset = [random(), random()];
+
+        let amplitude = self.amplitude * (1.0 - donut::ease(1.0 - self.strength, self.easing));
+        self.strength *= self.decay;
+
+        // Stop once it's too small to be seen
+        if self.amplitude * self.strength < 0.1 {
+            self.strength = 0.0;
+        }
+
+        [offset[0] * amplitude, offset[1] * amplitude]
+    }
+}
+
+fn config(args: &args::Args) -> RenderConfig {
+    // Start from the preset, if there is one, then let the other options change it. Preset names
+    // are checked as they're parsed.
+    let mut config = match &args.preset {
+        Some(name) => donut::preset(name, SCREEN_WIDTH, SCREEN_HEIGHT).unwrap(),
+        None => RenderConfig::new(SCREEN_WIDTH, SCREEN_HEIGHT),
+    };
+    if let Some(r1) = args.r1 {
+        config.r1 = r1.clamp(R1_RANGE.0, R1_RANGE.1);
+    }
+    if let Some(r2) = args.r2 {
+        config.r2 = r2.clamp(R2_RANGE.0, R2_RANGE.1);
+    }
+    config.auto_frame = args.auto_frame;
+    config.silhouette = args.silhouette;
+    if let Some(z_mode) = args.z_mode {
+        config.z_mode = z_mode;
+    }
+    config.checker = args.checker;
+    config.texture = args.texture.as_deref().map(load_texture);
+    if let Some(segments) = args.explode {
+        config.explode_segments = segments;
+    }
+    if let Some(grain) = args.grain {
+        config.grain = grain;
+    }
+    if let Some(strength) = args.bloom {
+        config.bloom_strength = strength;
+    }
+    if let Some(threshold) = args.bloom_threshold {
+        config.bloom_threshold = threshold;
+    }
+    config.outline_glow = args.outline_glow;
+    if let Some(radius) = args.outline_glow_radius {
+        config.outline_glow_radius = radius;
+    }
+    if let Some(intensity) = args.outline_glow_intensity {
+        config.outline_glow_intensity = intensity;
+    }
+    if let Some(gamma) = args.shading_gamma {
+        config.shading_gamma = gamma;
+    }
+    if let Some(floor) = args.lum_floor {
+        config.lum_floor = floor;
+    }
+    if let Some(ceil) = args.lum_ceil {
+        config.lum_ceil = ceil;
+    }
+    if let Some(radius) = args.splat {
+        config.splat = Some(radius);
+    }
+    config.cel = args.cel.map(|bands| bands.max(1));
+    config.fur = args.fur;
+    config.debug_axes = args.debug_axes;
+    config.debug_density = args.debug_density;
+    config.debug_overdraw = args.debug_overdraw;
+    config.cage = args.cage;
+    config.crisp_outline = args.crisp_outline;
+    if let Some(thickness) = args.shell_thickness {
+        config.shell_thickness = thickness;
+    }
+    if let Some(bias) = args.z_bias {
+        config.z_bias = bias;
+    }
+    if let Some(epsilon) = args.z_blend_epsilon {
+        config.z_blend_epsilon = epsilon;
+    }
+    if let Some(factor) = args.z_supersample {
+        config.z_supersample = factor.max(1);
+    }
+    if let Some(threshold) = args.cull_threshold {
+        config.cull_threshold = threshold;
+    }
+    if let Some(deadzone) = args.terminator_deadzone {
+        config.terminator_deadzone = deadzone;
+    }
+    if let Some(sparkle) = args.sparkle {
+        config.sparkle = sparkle;
+    }
+    if let Some(reflection) = args.reflection {
+        config.reflection = reflection;
+    }
+    if let Some(scale) = args.scale {
+        config.scale = scale;
+    }
+    if let Some(depth) = args.color_depth {
+        config.color_depth = depth;
+    }
+    if let Some(easing) = args.easing {
+        config.easing = easing;
+    }
+    if let Some(vignette) = args.vignette {
+        config.vignette = vignette;
+    }
+    if let Some(scanlines) = args.scanlines {
+        config.scanlines = scanlines;
+    }
+    if let Some(size) = args.point_size {
+        config.point_size = size;
+    }
+    if let Some(frames) = args.fade_in {
+        config.fade_in_frames = frames;
+    }
+    if let Some(frames) = args.reveal {
+        config.reveal_frames = frames;
+    }
+    config.interlace = args.interlace;
+    if let Some(shape) = args.shape {
+        config.shape = shape;
+    }
+    if args.anaglyph {
+        config.anaglyph = Some(args.eye_separation.unwrap_or(EYE_SEPARATION));
+    }
+    if let Some(frames) = args.spin_up {
+        config.spin_up_frames = frames;
+    }
+    if let Some(amplitude) = args.wobble {
+        config.wobble_amplitude = amplitude;
+    }
+    if let Some(frequency) = args.wobble_frequency {
+        config.wobble_frequency = frequency;
+    }
+    config.inner_color = args.inner_color;
+    config.outer_color = args.outer_color;
+    if let Some(palette) = &args.palette {
+        config.palette = palette.clone();
+    }
+    if let Some(color) = args.mono {
+        config.palette = donut::mono_palette(color, MONO_SHADES);
+    }
+    if let Some(cosine) = args.cosine_palette {
+        config.cosine_palette = Some(cosine);
+    }
+    if let Some(morph) = args.morph {
+        config.morph = morph;
+    }
+
+    // Zoomed in, the samples need to be as much closer together to still meet up
+    if let Some(crop @ [_, _, width, height]) = args.crop {
+        config.crop = Some(crop);
+        let zoom = (SCREEN_WIDTH as f32 / width).max(SCREEN_HEIGHT as f32 / height);
+        config.theta_spacing /= zoom.clamp(1.0, MAX_CROP_ZOOM);
+        config.phi_spacing /= zoom.clamp(1.0, MAX_CROP_ZOOM);
+    }
+    if let Some(spacing) = args.theta_spacing {
+        config.theta_spacing = spacing;
+    }
+    if let Some(spacing) = args.phi_spacing {
+        config.phi_spacing = spacing;
+    }
+    if args.auto_tune {
+        donut::auto_tune_spacing(&mut config);
+        if !args.quiet {
+            eprintln!(
+                "Sampling with --theta-spacing {} --phi-spacing {}",
+                config.theta_spacing, config.phi_spacing
+            );
+        }
+    }
+
+    if let Some(warning) = donut::check_sampling_density(&config) {
+        eprintln!("Warning: {} (try a larger --point-size)", warning);
+    }
+
+    config
+}
+
+// Update the parts of the config which change over time
+fn animate(args: &args::Args, config: &mut RenderConfig, frame: u64) {
+    if args.explode.is_some() {
+        // Ease out from whole to fully exploded and back again, (1 - cos) / 2 goes 0 -> 1 -> 0
+        // over each period.
+        let amplitude = args.explode_amplitude.unwrap_or(EXPLODE_AMPLITUDE);
+        let period = args.explode_period.unwrap_or(EXPLODE_PERIOD);
+        let t = (frame % period) as f32 / period as f32;
+        let (_, cos) = donut::sin_cos(2.0 * PI * t);
+        config.explode_distance = amplitude * (1.0 - cos) / 2.0;
+    }
+
+    // Morph smoothly from torus to sphere and back, in the same way as exploding
+    if let Some(period) = args.morph_period {
+        let t = (frame % period.max(1)) as f32 / period.max(1) as f32;
+        let (_, cos) = donut::sin_cos(2.0 * PI * t);
+        config.morph = (1.0 - cos) / 2.0;
+    }
+
+    // Melt gradually, then stay melted
+    if let Some(frames) = args.melt {
+        config.melt = donut::ease(frame as f32 / frames.max(1) as f32, config.easing);
+    }
+
+    if let Some(speed) = args.gradient_scroll {
+        config.gradient_scroll = frame as f32 * speed;
+    }
+
+    if let Some(seed) = args.proc_palette {
+        let frames = args
+            .proc_palette_frames
+            .unwrap_or(PROC_PALETTE_FRAMES)
+            .max(1);
+        config.palette = donut::proc_palette(seed, frame as f32 / frames as f32);
+    }
+
+    config.sparkle_direction = donut::sparkle_direction(frame);
+
+    // Alternate between the even and odd rows
+    config.interlace_field = (frame % 2) as usize;
+}
+
+// Reset the output to the background before drawing the next frame. When interlacing only the rows
+// about to be drawn are reset, the others keep showing the previous frame.
+fn clear(config: &RenderConfig, output: &mut [u32], background: &[u32]) {
+    if !config.interlace {
+        output.copy_from_slice(background);
+        return;
+    }
+
+    for (y, (row, background)) in output
+        .chunks_mut(config.width)
+        .zip(background.chunks(config.width))
+        .enumerate()
+    {
+        if y % 2 == config.interlace_field {
+            row.copy_from_slice(background);
+        }
+    }
+}
+
+// How fast to spin this frame as a fraction of full speed: accelerating from rest over the spin-up
+// frames, following the easing
+fn spin_up(config: &RenderConfig, frame: u64) -> f32 {
+    if frame < config.spin_up_frames {
+        donut::ease(frame as f32 / config.spin_up_frames as f32, config.easing)
+    } else {
+        1.0
+    }
+}
+
+// How far the wobble tilts the donut this frame, on top of its spin
+fn wobble(config: &RenderConfig, frame: u64) -> f32 {
+    let (sin, _) = donut::sin_cos(frame as f32 * config.wobble_frequency);
+    config.wobble_amplitude * sin
+}
+
+// Render a frame however the options lay it out, returning the z-buffer it was drawn with
+fn render(
+    args: &args::Args,
+    config: &mut RenderConfig,
+    a: f32,
+    b: f32,
+    output: &mut [u32],
+) -> Vec<Depth> {
+    // Rock back and forth rather than spinning round, if limited
+    let a = args.a_range.map_or(a, |range| donut::bounce(a, range));
+    let b = args.b_range.map_or(b, |range| donut::bounce(b, range));
+
+    // Turn the light along with the donut for this frame, then put it back so keys keep moving
+    // it around the donut rather than the screen
+    let light = config.light;
+    if args.camera_orbit {
+        config.light = donut::orbit_light(light, a, b);
+    }
+
+    let mut zbuffer = vec![0.0; config.width * config.height];
+    match (args.row, args.ring) {
+        (Some(n), _) => render_row_depth(config, n, a, b, output, &mut zbuffer),
+        (None, Some(n)) => {
+            let radius = args.ring_radius.unwrap_or(RING_RADIUS);
+            donut::render_ring_depth(config, n, radius, a, b, output, &mut zbuffer)
+        }
+        (None, None) => render_frame_depth(config, a, b, output, &mut zbuffer),
+    }
+
+    config.light = light;
+    zbuffer
+}
+
+// Swing the light around the viewer: yaw turns it left/right around the y-axis, pitch turns it
+// up/down around the x-axis.
+fn rotate_light(light: [f32; 3], yaw: f32, pitch: f32) -> [f32; 3] {
+    let [x, y, z] = light;
+    let (sin_yaw, cos_yaw) = donut::sin_cos(yaw);
+    let (sin_pitch, cos_pitch) = donut::sin_cos(pitch);
+
+    let (x, z) = (x * cos_yaw - z * sin_yaw, x * sin_yaw + z * cos_yaw);
+    let (y, z) = (y * cos_pitch - z * sin_pitch, y * sin_pitch + z * cos_pitch);
+
+    // Renormalize so rounding errors don't accumulate over many frames
+    let length = (x * x + y * y + z * z).sqrt();
+    [x / length, y / length, z / length]
+}
+
+fn open_image(path: &str) -> image::DynamicImage {
+    image::open(path).unwrap_or_else(|e| {
+        panic!("{}: {}", path, e);
+    })
+}
+
+fn to_pixels(image: &image::RgbImage) -> Vec<u32> {
+    image
+        .pixels()
+        .map(|p| (p[0] as u32) << 16 | (p[1] as u32) << 8 | p[2] as u32)
+        .collect()
+}
+
+// Load an image to sit behind the donut, resized to the render resolution
+fn load_background(path: &str) -> Vec<u32> {
+    let image = open_image(path)
+        .resize_exact(
+            SCREEN_WIDTH as u32,
+            SCREEN_HEIGHT as u32,
+            image::imageops::FilterType::Triangle,
+        )
+        .to_rgb8();
+
+    to_pixels(&image)
+}
+
+// What each frame is drawn over: the --background-image, or black
+fn background(args: &args::Args) -> Vec<u32> {
+    match &args.background_image {
+        Some(path) => load_background(path),
+        None => vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+    }
+}
+
+// Load an image to wrap around the donut at its original resolution
+fn load_texture(path: &str) -> Texture {
+    let image = open_image(path).to_rgb8();
+
+    Texture {
+        width: image.width() as usize,
+        height: image.height() as usize,
+        pixels: to_pixels(&image),
+    }
+}
+
+// With --resume, where the animation state is kept between runs
+fn state_path() -> Option<std::path::PathBuf> {
+    std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".donut-state.json"))
+}
+
+// Pick up where the last --resume run left off, or start from the beginning if there's nothing
+// (readable) saved
+fn load_state(args: &args::Args) -> AnimState {
+    let saved = if args.resume {
+        state_path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+    } else {
+        None
+    };
+
+    // Saved states are already offset
+    saved.unwrap_or_else(|| start_state(args))
+}
+
+// The beginning of the animation: at a random orientation with --random-start, and turned
+// --phase-offset further around the z-axis
+fn start_state(args: &args::Args) -> AnimState {
+    let mut state = if args.random_start {
+        random_start()
+    } else {
+        AnimState::default()
+    };
+
+    state.b += args.phase_offset.unwrap_or(0.0);
+    state
+}
+
+// An orientation picked from the clock, so each run starts somewhere different
+fn random_start() -> AnimState {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64);
+
+    // Two angles from 0 to 2 * PI, a thousandth of a radian apart
+    let steps = (2000.0 * PI) as u64;
+    AnimState {
+        a: (nanos % steps) as f32 / 1000.0,
+        b: (nanos / steps % steps) as f32 / 1000.0,
+        frame: 0,
+    }
+}
+
+fn save_state(args: &args::Args, state: &AnimState) {
+    if !args.resume {
+        return;
+    }
+
+    if let Some(path) = state_path() {
+        let json = serde_json::to_string(state).unwrap();
+        if let Err(e) = std::fs::write(&path, json) {
+            eprintln!("{}: {}", path.display(), e);
+        }
+    }
+}
+
+// Post-process a frame the donut has been drawn into, which was first filled with `background`,
+// ready to show. `level` is how loud the audio is, which brightens it.
+fn post_process(
+    config: &RenderConfig,
+    frame: u64,
+    level: f32,
+    background: &[u32],
+    display: &mut [u32],
+) {
+    if let Some(color) = config.outline_glow {
+        post::outline_glow(
+            display,
+            background,
+            SCREEN_WIDTH,
+            SCREEN_HEIGHT,
+            color,
+            config.outline_glow_radius,
+            config.outline_glow_intensity,
+        );
+    }
+    post::bloom(
+        display,
         SCREEN_WIDTH,
         SCREEN_HEIGHT,
-        WindowOptions {
-            scale: Scale::X4,
-            ..WindowOptions::default()
-        },
-    )
-    .unwrap_or_else(|e| {
-        panic!("{}", e);
+        config.bloom_threshold,
+        config.bloom_strength,
+    );
+    post::vignette(display, SCREEN_WIDTH, SCREEN_HEIGHT, config.vignette);
+    post::scanlines(display, SCREEN_WIDTH, config.scanlines);
+    post::grain(display, SCREEN_WIDTH, config.grain, frame);
+
+    // Ramp up so the first frame is only just visible and the last frame of the fade is
+    // fully opaque
+    if frame < config.fade_in_frames {
+        let t = (frame + 1) as f32 / config.fade_in_frames as f32;
+        let opacity = donut::ease(t, config.easing);
+        post::fade(display, background, opacity);
+    }
+
+    // The same for wiping in, the first frame showing the top row
+    if frame < config.reveal_frames {
+        let t = (frame + 1) as f32 / config.reveal_frames as f32;
+        let progress = donut::ease(t, config.easing);
+        post::reveal(display, background, SCREEN_WIDTH, progress);
+    }
+
+    post::brighten(display, 1.0 + AUDIO_BRIGHTEN * level);
+    post::reduce_color_depth(display, config.color_depth);
+}
+
+// Draw the frame `state` is on into `output`, which already holds whatever it's drawn over, then
+// post-process it into `display` to be shown. Returns the z-buffer it was drawn with.
+fn draw(
+    args: &args::Args,
+    config: &mut RenderConfig,
+    state: &AnimState,
+    level: f32,
+    background: &[u32],
+    output: &mut [u32],
+    display: &mut [u32],
+) -> Vec<Depth> {
+    let a = state.a + wobble(config, state.frame);
+    let zbuffer = render(args, config, a, state.b, output);
+
+    display.copy_from_slice(output);
+    post_process(config, state.frame, level, background, display);
+    zbuffer
+}
+
+// Draw frames into `target` until it's closed
+fn run(args: &args::Args, target: &mut dyn RenderTarget) {
+    // Each frame starts from a copy of the background, so anywhere the donut doesn't cover shows
+    // through.
+    let background = background(args);
+
+    // The donut is drawn into output, then post-processed into display. Keeping them apart means
+    // anything left over from the previous frame isn't post-processed twice.
+    let mut output = background.clone();
+    let mut display = background.clone();
+    let mut config = config(args);
+
+    let mut state = load_state(args);
+    let first_frame = state.frame;
+    let start = std::time::Instant::now();
+
+    let audio = audio::Audio::listen(args.quiet);
+
+    let fade_frames = args.palette_fade.unwrap_or(PALETTE_FADE_FRAMES);
+    let mut palettes = PaletteCycle::new(&config.palette, fade_frames, config.easing);
+
+    // What the options color the donut with, which P fades away from and back to
+    let (palette, cosine_palette) = (config.palette.clone(), config.cosine_palette);
+
+    let mut shake = Shake {
+        amplitude: 0.0,
+        decay: args.shake_decay.unwrap_or(SHAKE_DECAY),
+        easing: config.easing,
+        strength: 0.0,
+        seed: 0x2545_f491,
+    };
+
+    // With --capture-turns, which turn around the x-axis the last frame was on
+    let mut turn = (state.a / (2.0 * PI)).floor() as i64;
+
+    // With --drive-stdin, the angles for each frame come from here instead
+    let mut driving = args.drive_stdin.then(|| std::io::stdin().lock().lines());
+
+    while target.is_open() {
+        // Stop where we are while the window is in the background, then carry on from the same
+        // frame when it comes back.
+        if !target.is_active() {
+            target.idle();
+            continue;
+        }
+
+        if let Some(lines) = &mut driving {
+            match read_angles(lines) {
+                Some((a, b)) => (state.a, state.b) = (a, b),
+                None => break,
+            }
+        }
+
+        // Put back what P faded away from, for `animate` to move on or P to fade from again
+        config.palette.clone_from(&palette);
+        config.cosine_palette = cosine_palette;
+        animate(args, &mut config, state.frame);
+
+        // With --max-speed old frames are drawn over rather than cleared, which leaves trails
+        // wherever the donut has moved away from.
+        if !args.max_speed {
+            clear(&config, &mut output, &background);
+        }
+
+        // IJKL move the light up, left, down, and right
+        let key_step = |key| {
+            if target.is_key_down(key) {
+                LIGHT_STEP
+            } else {
+                0.0
+            }
+        };
+        let yaw = key_step(Key::L) - key_step(Key::J);
+        let pitch = key_step(Key::I) - key_step(Key::K);
+        if yaw != 0.0 || pitch != 0.0 {
+            config.light = rotate_light(config.light, yaw, pitch);
+        }
+
+        // [ ] shrink and grow the tube, - = the ring
+        let key_step = |shrink, grow| {
+            if target.is_key_down(grow) {
+                RADIUS_STEP
+            } else if target.is_key_down(shrink) {
+                -RADIUS_STEP
+            } else {
+                0.0
+            }
+        };
+        config.r1 = (config.r1 + key_step(Key::LeftBracket, Key::RightBracket))
+            .clamp(R1_RANGE.0, R1_RANGE.1);
+        config.r2 = (config.r2 + key_step(Key::Minus, Key::Equal)).clamp(R2_RANGE.0, R2_RANGE.1);
+
+        // `animate` has already moved --proc-palette on if it's changing
+        let start = match &config.cosine_palette {
+            Some(cosine) => sample_cosine(cosine),
+            None => config.palette.clone(),
+        };
+        if target.is_key_pressed(Key::P) {
+            palettes.next(&start);
+        }
+        if let Some(palette) = palettes.step(&start) {
+            config.palette = palette;
+            config.cosine_palette = None;
+        }
+
+        if target.is_key_pressed(Key::X) {
+            shake.start(args.shake_amplitude.unwrap_or(SHAKE_AMPLITUDE));
+        }
+        config.screen_offset = shake.next_offset();
+
+        let level = audio.as_ref().map_or(0.0, audio::Audio::level);
+        let zbuffer = draw(
+            args,
+            &mut config,
+            &state,
+            level,
+            &background,
+            &mut output,
+            &mut display,
+        );
+
+        if args.stats {
+            let stats = donut::depth_stats(&zbuffer);
+            eprintln!("frame {}: {}", state.frame, stats);
+        }
+
+        // What it would look like without the donut, for targets which only draw where it is
+        if let Some(plain) = target.background() {
+            plain.clone_from(&background);
+            post_process(&config, state.frame, level, &background, plain);
+        }
+
+        // Capture the first frame of each new turn
+        if let Some(pattern) = &args.capture_turns {
+            let this_turn = (state.a / (2.0 * PI)).floor() as i64;
+            if this_turn > turn {
+                save_screenshot(&display, &pattern.replace("{}", &this_turn.to_string()));
+            }
+            turn = this_turn;
+        }
+
+        if driving.is_some() {
+            state.frame += 1;
+        } else {
+            let speed = spin_up(&config, state.frame) * (1.0 + AUDIO_SPIN * level);
+            state.step_by(
+                args.speed_a.unwrap_or(donut::A_STEP) * speed,
+                args.speed_b.unwrap_or(donut::B_STEP) * speed,
+            );
+        }
+
+        target.present(&display, SCREEN_WIDTH, SCREEN_HEIGHT);
+    }
+
+    save_state(args, &state);
+
+    // Print the options to get back to the shape the keys were used to find
+    if args.dump_config {
+        println!(
+            "--r1 {} --r2 {} --theta-spacing {} --phi-spacing {}",
+            config.r1, config.r2, config.theta_spacing, config.phi_spacing
+        );
+    }
+
+    if args.max_speed && !args.quiet {
+        let frames = state.frame - first_frame;
+        let elapsed = start.elapsed();
+        eprintln!(
+            "{} frames in {:.2?} ({:.1} fps)",
+            frames,
+            elapsed,
+            frames as f64 / elapsed.as_secs_f64()
+        );
+    }
+}
+
+// The next pair of angles, a and b separated by whitespace, from lines like those from stdin,
+// skipping (with a warning) any lines which aren't. None once the lines run out.
+fn read_angles(lines: &mut impl Iterator<Item = std::io::Result<String>>) -> Option<(f32, f32)> {
+    for line in lines {
+        let line = line.unwrap_or_else(|e| panic!("stdin: {}", e));
+
+        let angles: Option<Vec<f32>> = line.split_whitespace().map(|s| s.parse().ok()).collect();
+        match angles.as_deref() {
+            Some(&[a, b]) => return Some((a, b)),
+            _ => eprintln!("Skipping invalid angles: {} (expected A B)", line),
+        }
+    }
+
+    None
+}
+
+// Like `run`, but replaying or recording the input if asked to
+fn run_with_input(args: &args::Args, target: &mut dyn RenderTarget) {
+    if let Some(path) = &args.replay_input {
+        run(args, &mut ReplayTarget::open(target, path));
+    } else if let Some(path) = &args.record_input {
+        run(args, &mut RecordingTarget::create(target, path));
+    } else {
+        run(args, target);
+    }
+}
+
+// Save a frame as it's shown, reporting rather than stopping for anything that goes wrong
+fn save_screenshot(display: &[u32], path: &str) {
+    let image = image::RgbImage::from_fn(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, |x, y| {
+        let pixel = display[y as usize * SCREEN_WIDTH + x as usize];
+        image::Rgb([(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8])
     });
 
-    // Limit to max ~60fps
-    window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
+    if let Err(e) = image.save(path) {
+        eprintln!("{}: {}", path, e);
+    }
+}
 
+// Write the luminance of the first frame to a 16-bit grayscale PNG, linearly scaled so 0..1 maps to
+// 0..65535
+fn export_png16(args: &args::Args, path: &str) {
     let mut output = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT];
-    let output_xy = |x, y| xy(SCREEN_WIDTH, x, y);
+    let mut luminance = vec![0.0; SCREEN_WIDTH * SCREEN_HEIGHT];
+    let mut config = config(args);
 
-    let mut a = 0.0;
-    let mut b = 0.0;
+    animate(args, &mut config, 0);
+    render_frame_luminance(&config, 0.0, 0.0, &mut output, &mut luminance);
+
+    let pixels = luminance
+        .iter()
+        .map(|l| (l.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16)
+        .collect();
+    let image = image::ImageBuffer::<image::Luma<u16>, Vec<u16>>::from_raw(
+        SCREEN_WIDTH as u32,
+        SCREEN_HEIGHT as u32,
+        pixels,
+    )
+    .unwrap();
+
+    image.save(path).unwrap_or_else(|e| {
+        panic!("{}: {}", path, e);
+    });
+}
 
-    while window.is_open() {
-        render_frame(a, b, &mut output, output_xy);
+// Render `--frames` frames of one full turn, post-processed like they're shown, and tile them into
+// one PNG, `--cols` frames across and as many rows as it takes. Without a --background-image,
+// anywhere that's left looking as it would without the donut is transparent, as are any unused
+// cells at the end.
+fn export_spritesheet(args: &args::Args, path: &str) {
+    let frames = args.frames.unwrap_or(SPRITESHEET_FRAMES);
+    let columns = args.cols.unwrap_or(SPRITESHEET_COLUMNS).max(1);
+    let rows = frames.div_ceil(columns);
 
-        a += 0.007;
-        b += 0.003;
+    let mut sheet = image::RgbaImage::new(
+        (columns * SCREEN_WIDTH) as u32,
+        (rows * SCREEN_HEIGHT) as u32,
+    );
+
+    let background = background(args);
+    let mut output = background.clone();
+    let mut display = background.clone();
+    let mut plain = background.clone();
+    let mut config = config(args);
+    let mut state = start_state(args);
+
+    for i in 0..frames {
+        animate(args, &mut config, state.frame);
+        output.copy_from_slice(&background);
+        draw(
+            args,
+            &mut config,
+            &state,
+            0.0,
+            &background,
+            &mut output,
+            &mut display,
+        );
+        plain.copy_from_slice(&background);
+        post_process(&config, state.frame, 0.0, &background, &mut plain);
+        state.turn(1.0 / frames as f32);
+
+        let (left, top) = (i % columns * SCREEN_WIDTH, i / columns * SCREEN_HEIGHT);
+        for (j, &pixel) in display.iter().enumerate() {
+            let shows_through = args.background_image.is_none() && pixel == plain[j];
+            let alpha = if shows_through { 0 } else { 0xff };
+            sheet.put_pixel(
+                (left + j % SCREEN_WIDTH) as u32,
+                (top + j / SCREEN_WIDTH) as u32,
+                image::Rgba([(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8, alpha]),
+            );
+        }
+    }
+
+    sheet.save(path).unwrap_or_else(|e| {
+        panic!("{}: {}", path, e);
+    });
+}
+
+// Render `--frames` frames of one full turn (by default as many as it takes at the usual speed),
+// post-processed like they're shown, and pipe them to ffmpeg to encode into an MP4, playing at
+// MP4_FPS
+fn export_mp4(args: &args::Args, path: &str) {
+    let frames = args
+        .frames
+        .unwrap_or((2.0 * PI / donut::A_STEP).round() as usize);
+
+    let mut ffmpeg = std::process::Command::new("ffmpeg")
+        .args([
+            "-y",
+            "-loglevel",
+            "error",
+            "-f",
+            "rawvideo",
+            "-pixel_format",
+            "rgba",
+        ])
+        .args([
+            "-video_size",
+            &format!("{}x{}", SCREEN_WIDTH, SCREEN_HEIGHT),
+        ])
+        .args(["-framerate", &MP4_FPS.to_string(), "-i", "-"])
+        .args(["-pix_fmt", "yuv420p", path])
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                eprintln!("--mp4 needs ffmpeg, which wasn't found on the PATH");
+            } else {
+                eprintln!("ffmpeg: {}", e);
+            }
+            std::process::exit(1);
+        });
+    let mut stdin = ffmpeg.stdin.take().unwrap();
+
+    let background = background(args);
+    let mut output = background.clone();
+    let mut display = background.clone();
+    let mut config = config(args);
+    let mut state = start_state(args);
+
+    for _ in 0..frames {
+        animate(args, &mut config, state.frame);
+        output.copy_from_slice(&background);
+        draw(
+            args,
+            &mut config,
+            &state,
+            0.0,
+            &background,
+            &mut output,
+            &mut display,
+        );
+        state.turn(1.0 / frames as f32);
+
+        let rgba: Vec<u8> = display
+            .iter()
+            .flat_map(|&pixel| [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8, 0xff])
+            .collect();
+
+        // ffmpeg only stops reading early if it's failed, which it's already said why on stderr
+        if stdin.write_all(&rgba).is_err() {
+            break;
+        }
+    }
+
+    // Closing stdin tells ffmpeg that's the last frame
+    drop(stdin);
+    let status = ffmpeg.wait().unwrap();
+    if !status.success() {
+        eprintln!("ffmpeg failed to write {} ({})", path, status);
+        std::process::exit(1);
+    }
+}
+
+fn main() {
+    let args = args::parse();
+
+    if args.list_presets {
+        for (name, description) in donut::PRESETS {
+            println!("{:<10} {}", name, description);
+        }
+    } else if let Some(path) = &args.png16 {
+        export_png16(&args, path);
+    } else if let Some(path) = &args.spritesheet {
+        export_spritesheet(&args, path);
+    } else if let Some(path) = &args.mp4 {
+        export_mp4(&args, path);
+    } else if let Some(path) = &args.fbdev {
+        run_with_input(&args, &mut fbdev::FbdevTarget::open(path, !args.max_speed));
+    } else if args.ascii_braille {
+        let char_aspect = args.char_aspect.unwrap_or(CHAR_ASPECT);
+        let mut target = BrailleTarget::new(!args.max_speed, char_aspect, args.region);
+        run_with_input(&args, &mut target);
+    } else {
+        let mut target = WindowTarget::new(SCREEN_WIDTH, SCREEN_HEIGHT, !args.max_speed);
+        run_with_input(&args, &mut target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Closes once it's been shown `frames` frames, keeping the last one
+    struct CountingTarget {
+        frames: usize,
+        presented: usize,
+        last: Vec<u32>,
+    }
+
+    impl RenderTarget for CountingTarget {
+        fn present(&mut self, buffer: &[u32], width: usize, height: usize) {
+            assert_eq!(buffer.len(), width * height);
+            self.presented += 1;
+            self.last = buffer.to_vec();
+        }
+
+        fn is_open(&self) -> bool {
+            self.presented < self.frames
+        }
+    }
+
+    #[test]
+    fn runs_until_the_target_closes() {
+        let mut target = CountingTarget {
+            frames: 3,
+            presented: 0,
+            last: Vec::new(),
+        };
+        run_with_input(&args::Args::default(), &mut target);
+
+        assert_eq!(target.presented, 3);
+        assert!(target.last.iter().any(|&pixel| pixel != 0));
+    }
+
+    // Closes after the first frame, keeping it and the background it was told the frame was drawn
+    // over, the way BrailleTarget uses it
+    #[derive(Default)]
+    struct BackgroundTarget {
+        last: Vec<u32>,
+        background: Vec<u32>,
+    }
+
+    impl RenderTarget for BackgroundTarget {
+        fn present(&mut self, buffer: &[u32], _width: usize, _height: usize) {
+            self.last = buffer.to_vec();
+        }
+
+        fn is_open(&self) -> bool {
+            self.last.is_empty()
+        }
+
+        fn background(&mut self) -> Option<&mut Vec<u32>> {
+            Some(&mut self.background)
+        }
+    }
 
-        window
-            .update_with_buffer(&output, SCREEN_WIDTH, SCREEN_HEIGHT)
+    #[test]
+    fn braille_background_is_post_processed_too() {
+        let path = std::env::temp_dir().join("donut-test-background.png");
+        image::RgbImage::from_pixel(10, 10, image::Rgb([128, 128, 128]))
+            .save(&path)
             .unwrap();
 
-        output = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let args = args::Args {
+            background_image: Some(path.to_str().unwrap().to_string()),
+            vignette: Some(0.8),
+            ..args::Args::default()
+        };
+        let mut target = BackgroundTarget::default();
+        run_with_input(&args, &mut target);
+
+        // The vignette darkens the corners well past what raises a dot, but the same happens to
+        // the background, so only the donut does
+        assert_ne!(target.last[0], 0x808080);
+        let text = braille::frame_to_braille(
+            &target.last,
+            &target.background,
+            SCREEN_WIDTH,
+            SCREEN_HEIGHT,
+            CHAR_ASPECT,
+        );
+        let rows: Vec<Vec<char>> = text.lines().map(|row| row.chars().collect()).collect();
+        for row in [rows.first().unwrap(), rows.last().unwrap()] {
+            assert_eq!((row[0], row[row.len() - 1]), ('\u{2800}', '\u{2800}'));
+        }
+        assert!(text.chars().any(|c| c != '\u{2800}' && c != '\n'));
+    }
+
+    #[test]
+    fn background_image_shows_wherever_the_donut_isnt() {
+        let path = std::env::temp_dir().join("donut-test-backdrop.png");
+        image::RgbImage::from_fn(40, 30, |x, y| image::Rgb([x as u8 * 6, y as u8 * 8, 200]))
+            .save(&path)
+            .unwrap();
+        let path = path.to_str().unwrap().to_string();
+
+        let last = |background_image| {
+            let mut target = CountingTarget {
+                frames: 1,
+                presented: 0,
+                last: Vec::new(),
+            };
+            let args = args::Args {
+                background_image,
+                theta_spacing: Some(0.02),
+                phi_spacing: Some(0.007),
+                ..args::Args::default()
+            };
+            run_with_input(&args, &mut target);
+            target.last
+        };
+        let plain = last(None);
+        let over_image = last(Some(path.clone()));
+        let image = load_background(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        // Over black, the donut is whatever isn't black
+        let mut uncovered = 0;
+        for ((&plain, &pixel), &backdrop) in plain.iter().zip(&over_image).zip(&image) {
+            if plain == 0 {
+                assert_eq!(pixel, backdrop);
+                uncovered += 1;
+            } else {
+                assert_eq!(pixel, plain);
+            }
+        }
+        assert!(uncovered > 0 && uncovered < plain.len());
+    }
+
+    #[test]
+    fn png16_stores_the_brightest_luminance_near_full_scale() {
+        let args = args::Args {
+            theta_spacing: Some(0.02),
+            phi_spacing: Some(0.007),
+            ..args::Args::default()
+        };
+        let path = std::env::temp_dir().join("donut-test-luminance.png");
+        export_png16(&args, path.to_str().unwrap());
+        let image = image::open(&path).unwrap().to_luma16();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            image.dimensions(),
+            (SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32)
+        );
+
+        let mut config = config(&args);
+        animate(&args, &mut config, 0);
+        let mut output = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let mut luminance = vec![0.0; SCREEN_WIDTH * SCREEN_HEIGHT];
+        render_frame_luminance(&config, 0.0, 0.0, &mut output, &mut luminance);
+        let brightest = luminance.iter().cloned().fold(0.0, f32::max);
+
+        let stored = *image.as_raw().iter().max().unwrap();
+        assert_eq!(stored, (brightest * u16::MAX as f32).round() as u16);
+        assert!(stored > u16::MAX / 10 * 9);
+    }
+
+    #[test]
+    fn fades_in_from_the_background() {
+        // The last of the first `frames` frames, with or without fading in over 10
+        let last = |fade_in, frames| {
+            let mut target = CountingTarget {
+                frames,
+                presented: 0,
+                last: Vec::new(),
+            };
+            let args = args::Args {
+                fade_in,
+                theta_spacing: Some(0.02),
+                phi_spacing: Some(0.007),
+                ..args::Args::default()
+            };
+            run_with_input(&args, &mut target);
+            target.last
+        };
+
+        // A tenth of the way in on the first frame, over black
+        let (first, unfaded) = (last(Some(10), 1), last(None, 1));
+        assert!(unfaded.iter().any(|&pixel| pixel > 0x202020));
+        for (&pixel, &unfaded) in first.iter().zip(&unfaded) {
+            for shift in [16, 8, 0] {
+                let (channel, unfaded) = ((pixel >> shift) & 0xff, (unfaded >> shift) & 0xff);
+                assert!(channel <= unfaded.div_ceil(10));
+            }
+        }
+
+        assert!(last(Some(10), 10) == last(None, 10));
+    }
+
+    #[test]
+    fn reveals_from_the_top_down() {
+        let mut config = RenderConfig::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+        config.reveal_frames = 10;
+        let background = vec![0x123456; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let mut rendered = background.clone();
+        donut::render_frame(&config, 1.0, 0.5, &mut rendered);
+
+        let shown = |frame| {
+            let mut display = rendered.clone();
+            post_process(&config, frame, 0.0, &background, &mut display);
+            display
+        };
+        let full = shown(10);
+
+        // Halfway down on the fifth frame, the donut having been there on both sides of the
+        // line, and all of the way on the last
+        let half = shown(4);
+        let split = SCREEN_WIDTH * SCREEN_HEIGHT / 2;
+        assert!(half[..split] == full[..split]);
+        assert!(half[split..].iter().all(|&pixel| pixel == 0x123456));
+        assert!(full[..split].iter().any(|&pixel| pixel != 0x123456));
+        assert!(full[split..].iter().any(|&pixel| pixel != 0x123456));
+        assert!(shown(9) == full);
+    }
+
+    #[test]
+    fn spins_up_from_rest() {
+        let mut config = RenderConfig::new(100, 100);
+        config.spin_up_frames = 10;
+
+        // How far each of the first 20 frames turns, as a fraction of full speed
+        let steps: Vec<f32> = (0..20).map(|frame| spin_up(&config, frame)).collect();
+
+        assert_eq!(steps[0], 0.0);
+        assert!(steps.windows(2).all(|w| w[0] <= w[1]));
+        assert!(steps[4] < steps[9]);
+        assert!(steps[10..].iter().all(|&step| step == 1.0));
+    }
+
+    #[test]
+    fn wobble_rocks_both_ways_within_the_amplitude() {
+        let mut config = RenderConfig::new(100, 100);
+        config.wobble_amplitude = 0.3;
+        config.wobble_frequency = 0.05;
+        let tilts: Vec<f32> = (0..1000).map(|frame| wobble(&config, frame)).collect();
+
+        assert!(tilts.iter().all(|tilt| tilt.abs() <= 0.3 + 1e-6));
+        assert!(tilts.iter().any(|&tilt| tilt > 0.29) && tilts.iter().any(|&tilt| tilt < -0.29));
+
+        config.wobble_amplitude = 0.0;
+        assert!((0..1000).all(|frame| wobble(&config, frame) == 0.0));
+    }
+
+    #[test]
+    fn random_start_turns_away_from_the_usual_start() {
+        assert_eq!(start_state(&args::Args::default()), AnimState::default());
+
+        let args = args::Args {
+            random_start: true,
+            ..args::Args::default()
+        };
+        let state = start_state(&args);
+        assert!(state.a != 0.0 || state.b != 0.0);
+        assert!((0.0..2.0 * PI).contains(&state.a) && (0.0..2.0 * PI).contains(&state.b));
+        assert_eq!(state.frame, 0);
+    }
+
+    // Like CountingTarget, but in the background for the `inactive` checks after the first frame
+    #[cfg(feature = "window-tests")]
+    struct BackgroundedTarget {
+        inactive: usize,
+        checks: usize,
+        idled: usize,
+        presented: Vec<Vec<u32>>,
+    }
+
+    #[cfg(feature = "window-tests")]
+    impl RenderTarget for BackgroundedTarget {
+        fn present(&mut self, buffer: &[u32], _width: usize, _height: usize) {
+            self.presented.push(buffer.to_vec());
+        }
+
+        fn is_open(&self) -> bool {
+            self.presented.len() < 2
+        }
+
+        fn is_active(&mut self) -> bool {
+            self.checks += 1;
+            !(2..2 + self.inactive).contains(&self.checks)
+        }
+
+        fn idle(&mut self) {
+            self.idled += 1;
+        }
+    }
+
+    #[cfg(feature = "window-tests")]
+    #[test]
+    fn pauses_in_the_background_and_carries_on_from_the_same_frame() {
+        let args = args::Args {
+            theta_spacing: Some(0.02),
+            phi_spacing: Some(0.007),
+            ..args::Args::default()
+        };
+        let frames = |inactive| {
+            let mut target = BackgroundedTarget {
+                inactive,
+                checks: 0,
+                idled: 0,
+                presented: Vec::new(),
+            };
+            run_with_input(&args, &mut target);
+            target
+        };
+
+        // Nothing's drawn while idling, and the second frame is the same however long that was
+        let (focused, backgrounded) = (frames(0), frames(5));
+        assert_eq!((focused.idled, backgrounded.idled), (0, 5));
+        assert_eq!(backgrounded.checks, focused.checks + 5);
+        assert!(backgrounded.presented == focused.presented);
+    }
+
+    #[test]
+    fn phase_offset_turns_the_start_around_the_z_axis() {
+        let offset = |phase_offset, random_start| args::Args {
+            phase_offset,
+            random_start,
+            ..args::Args::default()
+        };
+
+        let state = start_state(&offset(Some(0.75), false));
+        assert_eq!((state.a, state.b, state.frame), (0.0, 0.75, 0));
+
+        // On top of wherever a random start picked
+        let random = start_state(&offset(Some(100.0), true));
+        assert!(random.b >= 100.0 && random.b < 100.0 + 2.0 * PI);
+    }
+
+    #[test]
+    fn captures_a_frame_each_turn() {
+        let directory = std::env::temp_dir().join("donut-test-turns");
+        std::fs::create_dir_all(&directory).unwrap();
+        let pattern = directory.join("turn-{}.png");
+        let args = args::Args {
+            capture_turns: Some(pattern.to_str().unwrap().to_string()),
+            speed_a: Some(0.4 * 2.0 * PI),
+            theta_spacing: Some(0.02),
+            phi_spacing: Some(0.007),
+            ..args::Args::default()
+        };
+
+        // Ten frames 0.4 turns apart get 3.6 turns round
+        let mut target = CountingTarget {
+            frames: 10,
+            presented: 0,
+            last: Vec::new(),
+        };
+        run_with_input(&args, &mut target);
+
+        let mut captured: Vec<String> = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        captured.sort();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(captured, ["turn-1.png", "turn-2.png", "turn-3.png"]);
+    }
+
+    #[test]
+    fn spritesheet_cells_match_the_frames_shown() {
+        let args = args::Args {
+            frames: Some(3),
+            cols: Some(2),
+            vignette: Some(0.5),
+            theta_spacing: Some(0.02),
+            phi_spacing: Some(0.007),
+            ..args::Args::default()
+        };
+        let path = std::env::temp_dir().join("donut-test-spritesheet.png");
+        export_spritesheet(&args, path.to_str().unwrap());
+        let sheet = image::open(&path).unwrap().to_rgba8();
+        assert_eq!(sheet.dimensions(), (2 * 100, 2 * 100));
+
+        let cell = |i: usize| {
+            let (left, top) = (i % 2 * SCREEN_WIDTH, i / 2 * SCREEN_HEIGHT);
+            (0..SCREEN_WIDTH * SCREEN_HEIGHT)
+                .map(|j| *sheet.get_pixel((left + j % 100) as u32, (top + j / 100) as u32))
+                .collect::<Vec<_>>()
+        };
+        for i in 0..3 {
+            assert!(cell(i).iter().any(|pixel| pixel[3] == 0xff));
+        }
+        assert!(cell(3).iter().all(|pixel| pixel[3] == 0));
+
+        // The first cell is the first frame, vignette and all
+        let mut target = CountingTarget {
+            frames: 1,
+            presented: 0,
+            last: Vec::new(),
+        };
+        run_with_input(&args, &mut target);
+        for (pixel, &shown) in cell(0).iter().zip(&target.last) {
+            let rgb = (pixel[0] as u32) << 16 | (pixel[1] as u32) << 8 | pixel[2] as u32;
+            assert_eq!(rgb, shown);
+        }
+    }
+
+    #[test]
+    fn exports_an_mp4_if_ffmpeg_is_installed() {
+        let installed = std::process::Command::new("ffmpeg")
+            .arg("-version")
+            .output()
+            .is_ok_and(|output| output.status.success());
+        if !installed {
+            eprintln!("skipping, ffmpeg isn't installed");
+            return;
+        }
+
+        let args = args::Args {
+            frames: Some(3),
+            theta_spacing: Some(0.02),
+            phi_spacing: Some(0.007),
+            ..args::Args::default()
+        };
+        let path = std::env::temp_dir().join("donut-test.mp4");
+        let _ = std::fs::remove_file(&path);
+        export_mp4(&args, path.to_str().unwrap());
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+    }
+
+    #[test]
+    fn reads_angle_pairs_skipping_bad_lines() {
+        let input = "0 0\nnot angles\n1.5\n1 0.5 2\n1 0.5\n";
+        let mut lines = input.lines().map(|line| Ok(line.to_string()));
+        let first = read_angles(&mut lines).unwrap();
+        let second = read_angles(&mut lines).unwrap();
+        assert_eq!((first, second), ((0.0, 0.0), (1.0, 0.5)));
+        assert_eq!(read_angles(&mut lines), None);
+
+        // Each pair is a frame of its own
+        let config = RenderConfig::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+        let frames = [first, second].map(|(a, b)| donut::frame(&config, a, b));
+        assert_ne!(frames[0], frames[1]);
+    }
+
+    #[test]
+    fn palette_cycle_fades_channel_by_channel_from_whatever_is_showing() {
+        let red = vec![0xff0000; 4];
+        let mut palettes = PaletteCycle::new(&red, 2, Easing::Linear);
+        assert_eq!(palettes.step(&red), None);
+
+        // Halfway to the gradient, each channel is the average of the two
+        palettes.next(&red);
+        let half = palettes.step(&red).unwrap();
+        let gradient = donut::default_gradient();
+        for (&blended, &to) in half.iter().zip(gradient) {
+            for shift in [16, 8, 0] {
+                let channel = |color: u32| (color >> shift) & 0xff;
+                let average = (channel(0xff0000) + channel(to)) / 2;
+                assert!(channel(blended).abs_diff(average) <= 1);
+            }
+        }
+        assert_eq!(palettes.step(&red).as_deref(), Some(gradient));
+
+        // On through white and back round to the first palette, which has changed since
+        palettes.next(&red);
+        palettes.step(&red);
+        palettes.step(&red);
+        palettes.next(&red);
+        let blue = vec![0x0000ff; 4];
+        palettes.step(&blue);
+        assert_eq!(palettes.step(&blue), Some(blue.clone()));
+        assert_eq!(palettes.step(&blue), None);
+    }
+
+    // Presses `key` on the first frame and closes after `frames`, keeping the last one shown
+    struct PressingTarget {
+        key: Key,
+        frames: usize,
+        presented: usize,
+        last: Vec<u32>,
+    }
+
+    impl RenderTarget for PressingTarget {
+        fn present(&mut self, buffer: &[u32], _width: usize, _height: usize) {
+            self.presented += 1;
+            self.last = buffer.to_vec();
+        }
+
+        fn is_open(&self) -> bool {
+            self.presented < self.frames
+        }
+
+        fn is_key_pressed(&self, key: Key) -> bool {
+            self.presented == 0 && key == self.key
+        }
+    }
+
+    #[test]
+    fn p_fades_away_from_a_cosine_palette() {
+        let args = args::Args {
+            cosine_palette: Some(donut::CosinePalette {
+                a: [0.5; 3],
+                b: [0.5; 3],
+                c: [1.0; 3],
+                d: [0.0, 0.33, 0.67],
+            }),
+            palette_fade: Some(1),
+            theta_spacing: Some(0.02),
+            phi_spacing: Some(0.007),
+            ..args::Args::default()
+        };
+        let last = |key| {
+            let mut target = PressingTarget {
+                key,
+                frames: 2,
+                presented: 0,
+                last: Vec::new(),
+            };
+            run_with_input(&args, &mut target);
+            target.last
+        };
+
+        assert_ne!(last(Key::P), last(Key::Space));
+    }
+
+    #[test]
+    fn replays_recorded_keys_on_the_same_frames() {
+        let path = std::env::temp_dir().join("donut-test-input.jsonl");
+        let path = path.to_str().unwrap().to_string();
+        let args = |record_input, replay_input| args::Args {
+            record_input,
+            replay_input,
+            theta_spacing: Some(0.02),
+            phi_spacing: Some(0.007),
+            ..args::Args::default()
+        };
+
+        let mut recorded = PressingTarget {
+            key: Key::P,
+            frames: 3,
+            presented: 0,
+            last: Vec::new(),
+        };
+        run_with_input(&args(Some(path.clone()), None), &mut recorded);
+
+        // Left to itself this would run for longer, and P is only pressed in the recording
+        let mut replayed = CountingTarget {
+            frames: 10,
+            presented: 0,
+            last: Vec::new(),
+        };
+        run_with_input(&args(None, Some(path.clone())), &mut replayed);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(replayed.presented, 3);
+        assert!(replayed.last == recorded.last);
     }
 }
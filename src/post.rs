@@ -0,0 +1,433 @@
+// Effects applied to a finished frame, after the donut has been rendered into it.
+
+use crate::ColorDepth;
+
+// Apply `f` to each 0..255 channel of a 0xRRGGBB color, clamping the results back into range
+fn map_channels(color: u32, f: impl Fn(f32) -> f32) -> u32 {
+    let channel = |shift: u32| {
+        let value = ((color >> shift) & 0xff) as f32;
+        (f(value).round().clamp(0.0, 255.0) as u32) << shift
+    };
+
+    channel(16) | channel(8) | channel(0)
+}
+
+fn channels(color: u32) -> [f32; 3] {
+    [
+        ((color >> 16) & 0xff) as f32,
+        ((color >> 8) & 0xff) as f32,
+        (color & 0xff) as f32,
+    ]
+}
+
+fn from_channels([r, g, b]: [f32; 3]) -> u32 {
+    let channel = |value: f32| value.round().clamp(0.0, 255.0) as u32;
+    channel(r) << 16 | channel(g) << 8 | channel(b)
+}
+
+// Perceived brightness of a color, 0..1 (Rec. 709 luma)
+pub(crate) fn brightness(color: u32) -> f32 {
+    let [r, g, b] = channels(color);
+    (0.2126 * r + 0.7152 * g + 0.0722 * b) / 255.0
+}
+
+// A cheap integer hash (a variant of the "lowbias32" finalizer) so grain is random-looking but the
+// same (x, y, frame) always gives the same value.
+fn hash(x: u32, y: u32, frame: u32) -> u32 {
+    let mut h =
+        x.wrapping_mul(0x8da6_b343) ^ y.wrapping_mul(0xd816_3841) ^ frame.wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^= h >> 16;
+    h
+}
+
+/// Film grain: nudge the brightness of every pixel up or down by up to `amount` (0..1 of full
+/// brightness). The noise depends only on the pixel position and `frame`, so it animates from
+/// frame to frame but can be reproduced exactly.
+pub fn grain(output: &mut [u32], width: usize, amount: f32, frame: u64) {
+    if amount == 0.0 {
+        return;
+    }
+
+    for (i, pixel) in output.iter_mut().enumerate() {
+        let n = hash((i % width) as u32, (i / width) as u32, frame as u32);
+
+        // Map the hash to -1..1
+        let n = n as f32 / u32::MAX as f32 * 2.0 - 1.0;
+
+        *pixel = map_channels(*pixel, |c| c + n * amount * 255.0);
+    }
+}
+
+// The blur used for bloom reaches this many pixels either side of a bright pixel
+const BLOOM_RADIUS: usize = 4;
+const BLOOM_SIGMA: f32 = 2.0;
+
+// Blur `buffer` along one axis with a normalized 1D kernel. Samples past the edge of the frame
+// count as black.
+fn blur_pass(
+    buffer: &[[f32; 3]],
+    width: usize,
+    height: usize,
+    kernel: &[f32],
+    horizontal: bool,
+) -> Vec<[f32; 3]> {
+    let radius = kernel.len() as isize / 2;
+    let mut blurred = vec![[0.0; 3]; buffer.len()];
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0.0; 3];
+
+            for (k, weight) in kernel.iter().enumerate() {
+                let offset = k as isize - radius;
+                let (sx, sy) = if horizontal {
+                    (x as isize + offset, y as isize)
+                } else {
+                    (x as isize, y as isize + offset)
+                };
+
+                if sx < 0 || sy < 0 || sx >= width as isize || sy >= height as isize {
+                    continue;
+                }
+
+                let sample = buffer[sy as usize * width + sx as usize];
+                for c in 0..3 {
+                    sum[c] += sample[c] * weight;
+                }
+            }
+
+            blurred[y * width + x] = sum;
+        }
+    }
+
+    blurred
+}
+
+// A normalized 1D Gaussian kernel reaching `radius` pixels either side of the middle
+fn gaussian_kernel(radius: usize, sigma: f32) -> Vec<f32> {
+    let kernel: Vec<f32> = (0..=2 * radius)
+        .map(|i| {
+            let d = i as f32 - radius as f32;
+            (-(d * d) / (2.0 * sigma * sigma)).exp()
+        })
+        .collect();
+    let total: f32 = kernel.iter().sum();
+    kernel.iter().map(|weight| weight / total).collect()
+}
+
+/// Bloom: make the brightest parts of the frame glow. Pixels brighter than `threshold` (0..1) are
+/// blurred and added back on top of the frame, scaled by `strength`.
+pub fn bloom(output: &mut [u32], width: usize, height: usize, threshold: f32, strength: f32) {
+    if strength == 0.0 {
+        return;
+    }
+
+    let bright: Vec<[f32; 3]> = output
+        .iter()
+        .map(|&pixel| {
+            if brightness(pixel) > threshold {
+                channels(pixel)
+            } else {
+                [0.0; 3]
+            }
+        })
+        .collect();
+
+    let kernel = gaussian_kernel(BLOOM_RADIUS, BLOOM_SIGMA);
+
+    // A 2D Gaussian is separable, so blur across then down rather than over the whole square
+    let glow = blur_pass(&bright, width, height, &kernel, true);
+    let glow = blur_pass(&glow, width, height, &kernel, false);
+
+    for (pixel, glow) in output.iter_mut().zip(glow) {
+        let [r, g, b] = channels(*pixel);
+        *pixel = from_channels([
+            r + glow[0] * strength,
+            g + glow[1] * strength,
+            b + glow[2] * strength,
+        ]);
+    }
+}
+
+/// Scale the brightness of every pixel by `factor`, clamping at white.
+pub fn brighten(output: &mut [u32], factor: f32) {
+    if factor == 1.0 {
+        return;
+    }
+
+    for pixel in output.iter_mut() {
+        *pixel = map_channels(*pixel, |c| c * factor);
+    }
+}
+
+/// Vignette: darken the frame towards its corners. At `strength` 0 nothing changes, at 1 the
+/// corners go completely black.
+pub fn vignette(output: &mut [u32], width: usize, height: usize, strength: f32) {
+    if strength == 0.0 {
+        return;
+    }
+
+    let (center_x, center_y) = (width as f32 / 2.0, height as f32 / 2.0);
+
+    // Measured so the corners are at distance 1
+    let corner = (center_x * center_x + center_y * center_y).sqrt();
+
+    for (i, pixel) in output.iter_mut().enumerate() {
+        let dx = (i % width) as f32 + 0.5 - center_x;
+        let dy = (i / width) as f32 + 0.5 - center_y;
+        let distance = (dx * dx + dy * dy).sqrt() / corner;
+
+        // Squaring the distance keeps the middle of the frame bright and falls away smoothly
+        let factor = 1.0 - strength * distance * distance;
+
+        *pixel = map_channels(*pixel, |c| c * factor);
+    }
+}
+
+/// CRT scanlines: darken every odd row by `strength`, 0 for none and 1 for black.
+pub fn scanlines(output: &mut [u32], width: usize, strength: f32) {
+    if strength == 0.0 || width == 0 {
+        return;
+    }
+
+    for row in output.chunks_mut(width).skip(1).step_by(2) {
+        for pixel in row {
+            *pixel = map_channels(*pixel, |c| c * (1.0 - strength));
+        }
+    }
+}
+
+/// Round each channel to as many levels as `depth` has bits for, then spread those levels back out
+/// over 0 to 255, like an old console's limited palette.
+pub fn reduce_color_depth(output: &mut [u32], depth: ColorDepth) {
+    if depth == ColorDepth::True {
+        return;
+    }
+
+    let [red, green, blue] = depth.bits().map(|bits| ((1 << bits) - 1) as f32);
+    for pixel in output.iter_mut() {
+        let [r, g, b] = channels(*pixel);
+        let reduce = |c: f32, levels: f32| (c / 255.0 * levels).round() / levels * 255.0;
+        *pixel = from_channels([reduce(r, red), reduce(g, green), reduce(b, blue)]);
+    }
+}
+
+/// Blend from `background` (at 0) to the rendered frame (at 1) by `opacity`, to fade the donut in.
+pub fn fade(output: &mut [u32], background: &[u32], opacity: f32) {
+    if opacity >= 1.0 {
+        return;
+    }
+
+    for (pixel, &behind) in output.iter_mut().zip(background) {
+        let [r, g, b] = channels(*pixel);
+        let [br, bg, bb] = channels(behind);
+
+        *pixel = from_channels([
+            br + (r - br) * opacity,
+            bg + (g - bg) * opacity,
+            bb + (b - bb) * opacity,
+        ]);
+    }
+}
+
+/// Wipe the donut in from the top: show the rendered frame down to `progress` of the way (0 to 1)
+/// from the top, and `background` below that.
+pub fn reveal(output: &mut [u32], background: &[u32], width: usize, progress: f32) {
+    if progress >= 1.0 || width == 0 {
+        return;
+    }
+
+    let height = output.len() / width;
+    let shown = ((height as f32 * progress.max(0.0)) as usize) * width;
+    output[shown..].copy_from_slice(&background[shown..]);
+}
+
+/// Glow in `color` around the outside of the donut: wherever the frame still shows `background`,
+/// blend towards `color` the more of the donut there is within about `radius` pixels, up to
+/// `intensity` of the way right next to it. The donut itself is left alone.
+pub fn outline_glow(
+    output: &mut [u32],
+    background: &[u32],
+    width: usize,
+    height: usize,
+    color: u32,
+    radius: usize,
+    intensity: f32,
+) {
+    if intensity == 0.0 || radius == 0 {
+        return;
+    }
+
+    let covered: Vec<bool> = output.iter().zip(background).map(|(a, b)| a != b).collect();
+    let mask: Vec<[f32; 3]> = covered
+        .iter()
+        .map(|&covered| if covered { [1.0; 3] } else { [0.0; 3] })
+        .collect();
+
+    // Right next to a straight edge about half the blurred mask is covered, so double it to reach
+    // the full intensity there
+    let kernel = gaussian_kernel(radius, radius as f32 / 2.0);
+    let spread = blur_pass(&mask, width, height, &kernel, true);
+    let spread = blur_pass(&spread, width, height, &kernel, false);
+
+    let glow = channels(color);
+    for ((pixel, spread), covered) in output.iter_mut().zip(spread).zip(covered) {
+        if !covered {
+            let t = (spread[0] * 2.0 * intensity).min(1.0);
+            let [r, g, b] = channels(*pixel);
+            *pixel = from_channels([
+                r + (glow[0] - r) * t,
+                g + (glow[1] - g) * t,
+                b + (glow[2] - b) * t,
+            ]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A frame with a bit of everything in it, to apply the effects to
+    fn test_frame() -> Vec<u32> {
+        (0..64 * 48)
+            .map(|i| (i as u32 * 0x010305) & 0xffffff)
+            .collect()
+    }
+
+    #[test]
+    fn grain_animates_but_reproduces() {
+        let frame = test_frame();
+        let grained = |amount, index| {
+            let mut output = frame.clone();
+            grain(&mut output, 64, amount, index);
+            output
+        };
+
+        assert!(grained(0.0, 7) == frame);
+        assert!(grained(0.1, 7) != frame);
+        assert!(grained(0.1, 7) == grained(0.1, 7));
+        assert!(grained(0.1, 7) != grained(0.1, 8));
+
+        // Never by more than the amount
+        for (&pixel, grained) in frame.iter().zip(grained(0.1, 7)) {
+            let (was, now) = (channels(pixel), channels(grained));
+            assert!((0..3).all(|c| (was[c] - now[c]).abs() <= 0.1 * 255.0 + 0.5));
+        }
+    }
+
+    #[test]
+    fn bloom_spreads_a_bright_pixel_into_a_halo() {
+        let (size, middle) = (21, 10);
+        let mut output = vec![0; size * size];
+        output[middle * size + middle] = 0xffffff;
+        bloom(&mut output, size, size, 0.8, 1.0);
+
+        let at = |dx: isize, dy: isize| {
+            let (x, y) = (middle as isize + dx, middle as isize + dy);
+            output[y as usize * size + x as usize]
+        };
+        assert_eq!(at(0, 0), 0xffffff);
+
+        // Fading out evenly in every direction, as far as the blur reaches and no further
+        let radius = BLOOM_RADIUS as isize;
+        for d in 1..=radius {
+            let glow = at(d, 0);
+            assert!(glow != 0 && glow < at(d - 1, 0));
+            assert!([at(-d, 0), at(0, d), at(0, -d)]
+                .iter()
+                .all(|&pixel| pixel == glow));
+        }
+        for (i, &pixel) in output.iter().enumerate() {
+            let distance = (i % size).abs_diff(middle).max((i / size).abs_diff(middle));
+            if distance > BLOOM_RADIUS {
+                assert_eq!(pixel, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn vignette_darkens_the_corners_more_than_the_middle() {
+        let (width, height) = (64, 48);
+        let mut output = vec![0x8090a0; width * height];
+        vignette(&mut output, width, height, 0.6);
+
+        let middle = brightness(output[height / 2 * width + width / 2]);
+        for corner in [0, width - 1, (height - 1) * width, width * height - 1] {
+            assert!(brightness(output[corner]) < middle - 0.2);
+        }
+        assert!((middle - brightness(0x8090a0)).abs() < 0.01);
+
+        let mut unchanged = test_frame();
+        vignette(&mut unchanged, 64, 48, 0.0);
+        assert!(unchanged == test_frame());
+    }
+
+    #[test]
+    fn scanlines_darken_the_odd_rows() {
+        let (width, height) = (64, 48);
+        let mut output = vec![0x8090a0; width * height];
+        scanlines(&mut output, width, 0.5);
+
+        for (y, row) in output.chunks(width).enumerate() {
+            let expected = if y % 2 == 0 { 0x8090a0 } else { 0x404850 };
+            assert!(row.iter().all(|&pixel| pixel == expected), "row {}", y);
+        }
+
+        let mut unchanged = test_frame();
+        scanlines(&mut unchanged, 64, 0.0);
+        assert!(unchanged == test_frame());
+    }
+
+    #[test]
+    fn rgb332_keeps_8_levels_of_red_and_green_and_4_of_blue() {
+        // Every color there is, spread out
+        let mut output: Vec<u32> = (0..1 << 16).map(|i| i * 0xff).collect();
+        reduce_color_depth(&mut output, ColorDepth::Rgb332);
+
+        let levels = |shift: u32| {
+            let mut values: Vec<u32> = output
+                .iter()
+                .map(|&pixel| (pixel >> shift) & 0xff)
+                .collect();
+            values.sort_unstable();
+            values.dedup();
+            values
+        };
+        assert_eq!(levels(16).len(), 8);
+        assert_eq!(levels(8).len(), 8);
+        assert_eq!(levels(0), [0, 85, 170, 255]);
+
+        let mut unchanged = test_frame();
+        reduce_color_depth(&mut unchanged, ColorDepth::True);
+        assert!(unchanged == test_frame());
+    }
+
+    #[test]
+    fn outline_glow_lights_up_only_the_background_near_the_donut() {
+        // A 10x10 square of "donut" in the middle of a 48x48 frame
+        let (size, background) = (48, vec![0x101010; 48 * 48]);
+        let mut output = background.clone();
+        for y in 19..29 {
+            output[y * size + 19..y * size + 29].copy_from_slice(&[0x808080; 10]);
+        }
+        let drawn = output.clone();
+        outline_glow(&mut output, &background, size, size, 0xff0000, 4, 1.0);
+
+        let red = |i: usize| channels(output[i])[0];
+        // Right next to the edge it's mostly the glow color, falling off further out, and gone by
+        // the corners of the frame
+        let (next_to, further) = (24 * size + 18, 24 * size + 15);
+        assert!(red(next_to) > 0.7 * 255.0);
+        assert!(red(further) > channels(0x101010)[0] && red(further) < red(next_to));
+        assert_eq!(output[0], 0x101010);
+        // The donut's untouched
+        for y in 19..29 {
+            assert!(output[y * size + 19..y * size + 29] == drawn[y * size + 19..y * size + 29]);
+        }
+    }
+}
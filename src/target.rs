@@ -0,0 +1,331 @@
+// Where finished frames are shown. The animation loop in main.rs draws each frame the same way and
+// hands it to one of these, which also reports back any keys the viewer is pressing.
+
+use crate::braille;
+use minifb::{Key, KeyRepeat, Scale, Window, WindowOptions};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::io::{BufRead, Write};
+
+// Limit to max ~60fps, unless we're benchmarking
+pub const FRAME_TIME: std::time::Duration = std::time::Duration::from_micros(16600);
+
+// How long to wait between checks for the window coming back into focus
+const IDLE_TIME: std::time::Duration = std::time::Duration::from_millis(100);
+
+pub trait RenderTarget {
+    // Show a `width * height` frame of 0xRRGGBB pixels
+    fn present(&mut self, buffer: &[u32], width: usize, height: usize);
+
+    // Whether to keep drawing frames
+    fn is_open(&self) -> bool {
+        true
+    }
+
+    // Whether `key` is held down, for targets which can tell
+    fn is_key_down(&self, _key: Key) -> bool {
+        false
+    }
+
+    // Whether `key` was pressed since the last frame, for targets which can tell
+    fn is_key_pressed(&self, _key: Key) -> bool {
+        false
+    }
+
+    // Whether anyone's watching, for targets which can tell. While nobody is, the last frame is
+    // left up and `idle` is called instead of drawing new ones.
+    fn is_active(&mut self) -> bool {
+        true
+    }
+
+    // Wait a while without drawing a new frame
+    fn idle(&mut self) {}
+
+    // Where to put what each frame would look like without the donut, for targets which only draw
+    // where it differs from that. It's filled in before each frame is presented.
+    fn background(&mut self) -> Option<&mut Vec<u32>> {
+        None
+    }
+}
+
+pub struct WindowTarget {
+    window: Window,
+
+    // Windows aren't focused until the window manager says so, which some never do, so only
+    // pause for losing focus once it's been had.
+    focused: bool,
+}
+
+impl WindowTarget {
+    pub fn new(width: usize, height: usize, limit_rate: bool) -> WindowTarget {
+        let mut window = Window::new(
+            "Donut",
+            width,
+            height,
+            WindowOptions {
+                scale: Scale::X4,
+                ..WindowOptions::default()
+            },
+        )
+        .unwrap_or_else(|e| {
+            panic!("{}", e);
+        });
+
+        window.limit_update_rate(if limit_rate { Some(FRAME_TIME) } else { None });
+
+        WindowTarget {
+            window,
+            focused: false,
+        }
+    }
+}
+
+impl RenderTarget for WindowTarget {
+    fn present(&mut self, buffer: &[u32], width: usize, height: usize) {
+        self.window
+            .update_with_buffer(buffer, width, height)
+            .unwrap();
+    }
+
+    fn is_open(&self) -> bool {
+        self.window.is_open() && !self.window.is_key_down(Key::Escape)
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        self.window.is_key_down(key)
+    }
+
+    fn is_key_pressed(&self, key: Key) -> bool {
+        self.window.is_key_pressed(key, KeyRepeat::No)
+    }
+
+    fn is_active(&mut self) -> bool {
+        let active = self.window.is_active();
+        self.focused |= active;
+        active || !self.focused
+    }
+
+    // Keep handling events, so we notice being closed or focused again
+    fn idle(&mut self) {
+        self.window.update();
+        std::thread::sleep(IDLE_TIME);
+    }
+}
+
+// Draws to the terminal in Braille characters, see braille.rs
+pub struct BrailleTarget {
+    stdout: std::io::StdoutLock<'static>,
+    limit_rate: bool,
+    char_aspect: f32,
+
+    // Where to draw, or None for the whole screen from the top-left corner
+    region: Option<braille::Region>,
+
+    // What the frames are drawn over, post-processed like they are, so only the donut raises dots
+    background: Vec<u32>,
+}
+
+impl BrailleTarget {
+    pub fn new(
+        limit_rate: bool,
+        char_aspect: f32,
+        region: Option<braille::Region>,
+    ) -> BrailleTarget {
+        let mut stdout = std::io::stdout().lock();
+
+        // Clear the screen once, then redraw each frame from the top-left corner. Drawing into a
+        // region leaves the rest of the screen to whatever else is using it.
+        if region.is_none() {
+            write!(stdout, "\x1b[2J").unwrap();
+        }
+
+        BrailleTarget {
+            stdout,
+            limit_rate,
+            char_aspect,
+            region,
+            background: Vec::new(),
+        }
+    }
+}
+
+impl RenderTarget for BrailleTarget {
+    fn present(&mut self, buffer: &[u32], width: usize, height: usize) {
+        match &self.region {
+            Some(region) => {
+                let text = braille::frame_to_braille_region(
+                    buffer,
+                    &self.background,
+                    width,
+                    height,
+                    self.char_aspect,
+                    region,
+                );
+                write!(self.stdout, "{}", text).unwrap();
+            }
+            None => {
+                let text = braille::frame_to_braille(
+                    buffer,
+                    &self.background,
+                    width,
+                    height,
+                    self.char_aspect,
+                );
+                write!(self.stdout, "\x1b[H{}", text).unwrap();
+            }
+        }
+        self.stdout.flush().unwrap();
+
+        if self.limit_rate {
+            std::thread::sleep(FRAME_TIME);
+        }
+    }
+
+    fn background(&mut self) -> Option<&mut Vec<u32>> {
+        Some(&mut self.background)
+    }
+}
+
+// The keys asked about during one frame which were down or had just been pressed, by name
+#[derive(Default, Serialize, Deserialize)]
+struct InputFrame {
+    down: Vec<String>,
+    pressed: Vec<String>,
+}
+
+fn key_name(key: Key) -> String {
+    format!("{:?}", key)
+}
+
+// Passes everything through to another target, writing down the input it reports as a line of
+// JSON per frame
+pub struct RecordingTarget<'a> {
+    inner: &'a mut dyn RenderTarget,
+    file: std::io::BufWriter<std::fs::File>,
+    frame: RefCell<InputFrame>,
+}
+
+impl<'a> RecordingTarget<'a> {
+    pub fn create(inner: &'a mut dyn RenderTarget, path: &str) -> RecordingTarget<'a> {
+        let file = std::fs::File::create(path).unwrap_or_else(|e| {
+            panic!("{}: {}", path, e);
+        });
+
+        RecordingTarget {
+            inner,
+            file: std::io::BufWriter::new(file),
+            frame: RefCell::default(),
+        }
+    }
+}
+
+impl RenderTarget for RecordingTarget<'_> {
+    fn present(&mut self, buffer: &[u32], width: usize, height: usize) {
+        let frame = self.frame.take();
+        serde_json::to_writer(&mut self.file, &frame).unwrap();
+        writeln!(self.file).unwrap();
+
+        self.inner.present(buffer, width, height);
+    }
+
+    fn is_open(&self) -> bool {
+        self.inner.is_open()
+    }
+
+    fn is_active(&mut self) -> bool {
+        self.inner.is_active()
+    }
+
+    fn idle(&mut self) {
+        self.inner.idle();
+    }
+
+    fn background(&mut self) -> Option<&mut Vec<u32>> {
+        self.inner.background()
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        let down = self.inner.is_key_down(key);
+        if down {
+            self.frame.borrow_mut().down.push(key_name(key));
+        }
+        down
+    }
+
+    fn is_key_pressed(&self, key: Key) -> bool {
+        let pressed = self.inner.is_key_pressed(key);
+        if pressed {
+            self.frame.borrow_mut().pressed.push(key_name(key));
+        }
+        pressed
+    }
+}
+
+// Shows frames on another target, but reports the input from a RecordingTarget's file instead of
+// the live input, frame by frame. Only which frame each key was used on is recorded, not when, so
+// the replay goes at the inner target's own frame rate rather than the recorded run's. It closes
+// once the recording runs out, so a run replayed from the same starting state ends up exactly
+// where the recorded one did.
+pub struct ReplayTarget<'a> {
+    inner: &'a mut dyn RenderTarget,
+    frames: Vec<InputFrame>,
+    frame: usize,
+}
+
+impl<'a> ReplayTarget<'a> {
+    pub fn open(inner: &'a mut dyn RenderTarget, path: &str) -> ReplayTarget<'a> {
+        let file = std::fs::File::open(path).unwrap_or_else(|e| {
+            panic!("{}: {}", path, e);
+        });
+        let frames = std::io::BufReader::new(file)
+            .lines()
+            .map(|line| {
+                let line = line.unwrap_or_else(|e| panic!("{}: {}", path, e));
+                serde_json::from_str(&line).unwrap_or_else(|e| panic!("{}: {}", path, e))
+            })
+            .collect();
+
+        ReplayTarget {
+            inner,
+            frames,
+            frame: 0,
+        }
+    }
+
+    fn recorded(&self) -> Option<&InputFrame> {
+        self.frames.get(self.frame)
+    }
+}
+
+impl RenderTarget for ReplayTarget<'_> {
+    fn present(&mut self, buffer: &[u32], width: usize, height: usize) {
+        self.frame += 1;
+        self.inner.present(buffer, width, height);
+    }
+
+    fn is_open(&self) -> bool {
+        self.inner.is_open() && self.frame < self.frames.len()
+    }
+
+    fn is_active(&mut self) -> bool {
+        self.inner.is_active()
+    }
+
+    fn idle(&mut self) {
+        self.inner.idle();
+    }
+
+    fn background(&mut self) -> Option<&mut Vec<u32>> {
+        self.inner.background()
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        self.recorded()
+            .is_some_and(|frame| frame.down.contains(&key_name(key)))
+    }
+
+    fn is_key_pressed(&self, key: Key) -> bool {
+        self.recorded()
+            .is_some_and(|frame| frame.pressed.contains(&key_name(key)))
+    }
+}
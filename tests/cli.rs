@@ -0,0 +1,28 @@
+// Run the donut binary itself, to check what it prints where
+
+use std::process::Command;
+
+// Render a --png16 frame with the sample spacing auto-tuned, which says what it picked unless
+// `--quiet`, returning what was printed to stderr
+fn auto_tune_stderr(extra: &[&str], name: &str) -> String {
+    let path = std::env::temp_dir().join(name);
+    let output = Command::new(env!("CARGO_BIN_EXE_donut"))
+        .args(["--png16", path.to_str().unwrap(), "--auto-tune"])
+        .args(extra)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn quiet_keeps_informational_messages_off_stderr() {
+    let loud = auto_tune_stderr(&[], "donut-test-loud.png");
+    assert!(loud.contains("Sampling with --theta-spacing"), "{}", loud);
+
+    let quiet = auto_tune_stderr(&["--quiet"], "donut-test-quiet.png");
+    assert_eq!(quiet, "");
+}